@@ -11,10 +11,22 @@
 #[macro_use] extern crate nom;
 #[macro_use] extern crate enum_primitive;
 extern crate num;
+extern crate md5;
 
-mod htype;
-mod op;
-mod options;
+/// Like `try!`, but for `Option`s
+macro_rules! try_opt(
+    ($e:expr) => (
+        match $e {
+            Some(v) => v,
+            None => return None,
+        }
+    )
+);
+
+pub mod htype;
+pub mod message;
+pub mod op;
+pub mod options;
 mod util;
 
 use std::fmt;
@@ -22,24 +34,101 @@ use std::error;
 use std::convert::{From};
 use std::net::{IpAddr, Ipv4Addr};
 use nom::{IResult, be_u8, be_u16, be_u32};
+use num::{FromPrimitive};
+
+use self::util::{take_rest, u16_bytes, u32_bytes, ip};
 
-use self::op::Op;
-use self::htype::Htype;
-use self::util::{take_rest};
-use self::options::{DhcpOption};
+pub use self::op::Op;
+pub use self::htype::Htype;
+pub use self::options::{DhcpOption, NodeType, OptionOverloadType, DhcpMessageTypes};
+pub use self::message::{Message, HardwareAddress};
 
 const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
 
-#[derive(Debug, Clone)]
+/// Everything that can go wrong while parsing or encoding a message
+///
+/// Every parse error carries the byte offset, from the start of the
+/// packet, at which the problem was found.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
-    ParseError(String),
+    /// The packet ended before the fixed header and magic cookie did.
+    /// `field` is the header field that was cut off.
+    TruncatedHeader { offset: usize, field: &'static str },
+    /// The four bytes after the fixed header were not the magic cookie
+    BadMagicCookie { offset: usize },
+    /// The `op` field was neither BOOTREQUEST nor BOOTREPLY
+    InvalidOp { offset: usize, value: u8 },
+    /// An option's length byte runs past the end of the option area,
+    /// or doesn't match the payload its type requires
+    OptionLengthMismatch { offset: usize, code: u8 },
+    /// An option's payload was the right length, but held a value its
+    /// type doesn't allow
+    InvalidOptionValue { offset: usize, code: u8 },
+    /// A string-valued option was not valid UTF-8
+    InvalidUtf8 { offset: usize, code: u8 },
+    /// A fixed-size string field in the header (`sname` or `file`) was
+    /// not a null-terminated UTF-8 string
+    InvalidString { offset: usize, field: &'static str },
+    /// There were bytes other than `Pad` after the `End` option
+    LeftoverInput { offset: usize },
+    /// The packet doesn't have an option that was needed, such as the
+    /// Authentication option when checking a MAC
+    MissingOption { code: u8 },
+    /// The message could not be represented on the wire
+    EncodeError(String),
+}
+
+impl Error {
+    /// Byte offset in the packet that the error refers to, if it came
+    /// from parsing
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            &Error::TruncatedHeader { offset, .. } => Some(offset),
+            &Error::BadMagicCookie { offset } => Some(offset),
+            &Error::InvalidOp { offset, .. } => Some(offset),
+            &Error::OptionLengthMismatch { offset, .. } => Some(offset),
+            &Error::InvalidOptionValue { offset, .. } => Some(offset),
+            &Error::InvalidUtf8 { offset, .. } => Some(offset),
+            &Error::InvalidString { offset, .. } => Some(offset),
+            &Error::LeftoverInput { offset } => Some(offset),
+            &Error::MissingOption { .. } => None,
+            &Error::EncodeError(_) => None,
+        }
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            &Error::ParseError(ref s) => {
-                write!(f, "{:?}", s)
+            &Error::TruncatedHeader { offset, field } => {
+                write!(f, "packet truncated at byte {}, in `{}`", offset, field)
+            }
+            &Error::BadMagicCookie { offset } => {
+                write!(f, "bad magic cookie at byte {}", offset)
+            }
+            &Error::InvalidOp { offset, value } => {
+                write!(f, "invalid op {} at byte {}", value, offset)
+            }
+            &Error::OptionLengthMismatch { offset, code } => {
+                write!(f, "option {} at byte {} has the wrong length", code, offset)
+            }
+            &Error::InvalidOptionValue { offset, code } => {
+                write!(f, "option {} at byte {} has an invalid value", code, offset)
+            }
+            &Error::InvalidUtf8 { offset, code } => {
+                write!(f, "option {} at byte {} is not valid UTF-8", code, offset)
+            }
+            &Error::InvalidString { offset, field } => {
+                write!(f, "`{}` at byte {} is not a null-terminated UTF-8 string", field, offset)
+            }
+            &Error::LeftoverInput { offset } => {
+                write!(f, "unexpected data after the End option at byte {}", offset)
+            }
+            &Error::MissingOption { code } => {
+                write!(f, "option {} is missing", code)
+            }
+            &Error::EncodeError(ref s) => {
+                write!(f, "{}", s)
             }
         }
     }
@@ -48,16 +137,23 @@ impl fmt::Display for Error {
 impl error::Error for Error {
     fn description(&self) -> &str {
         match self {
-            &Error::ParseError(ref s) => {
-                s
-            }
+            &Error::TruncatedHeader { .. } => "truncated header",
+            &Error::BadMagicCookie { .. } => "bad magic cookie",
+            &Error::InvalidOp { .. } => "invalid op",
+            &Error::OptionLengthMismatch { .. } => "option length mismatch",
+            &Error::InvalidOptionValue { .. } => "invalid option value",
+            &Error::InvalidUtf8 { .. } => "invalid UTF-8 in option",
+            &Error::InvalidString { .. } => "invalid string field",
+            &Error::LeftoverInput { .. } => "leftover input",
+            &Error::MissingOption { .. } => "missing option",
+            &Error::EncodeError(ref s) => s,
         }
     }
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 /// Data type that the bytes get translated into.
 ///
 /// In some cases I translated them into more specific data types
@@ -84,28 +180,296 @@ pub struct RawMessage<'a> {
     options: Vec<DhcpOption>,
 }
 
-#[allow(dead_code)]
-fn parse_message<'a>(bytes: &'a [u8]) -> Result<RawMessage<'a>> {
-    match _parse_message(bytes) {
-        IResult::Done(inp, msg) => {
-            if inp.len() > 0 {
-                return Err(Error::ParseError("LEFTOVER INPUT".into()));
-            }
-            Ok(msg)
-        },
-        IResult::Error(err) => {
-            Err(Error::ParseError(format!("SOME OTHER ERROR: {:?}", err)))
+impl<'a> RawMessage<'a> {
+    /// Message op code
+    pub fn op(&self) -> Op {
+        self.op
+    }
+
+    /// Hardware address type
+    pub fn htype(&self) -> Htype {
+        self.htype
+    }
+
+    /// Hardware address length
+    pub fn hlen(&self) -> u8 {
+        self.hlen
+    }
+
+    /// Whether `hlen` is what `htype` calls for. Hardware types without
+    /// a fixed address length only need `hlen` to fit in `chaddr`.
+    pub fn hlen_is_valid(&self) -> bool {
+        match self.htype.address_len() {
+            Some(len) => self.hlen == len,
+            None => self.hlen as usize <= self.chaddr.len(),
+        }
+    }
+
+    /// Number of relay agent hops
+    pub fn hops(&self) -> u8 {
+        self.hops
+    }
+
+    /// Transaction ID
+    pub fn xid(&self) -> u32 {
+        self.xid
+    }
+
+    /// Seconds elapsed since the client began acquiring an address
+    pub fn secs(&self) -> u16 {
+        self.secs
+    }
+
+    /// Flags field; the high bit is the broadcast flag
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Client IP address
+    pub fn ciaddr(&self) -> IpAddr {
+        self.ciaddr
+    }
+
+    /// "Your" (client) IP address
+    pub fn yiaddr(&self) -> IpAddr {
+        self.yiaddr
+    }
+
+    /// IP address of the next server to use in bootstrap
+    pub fn siaddr(&self) -> IpAddr {
+        self.siaddr
+    }
+
+    /// Relay agent IP address
+    pub fn giaddr(&self) -> IpAddr {
+        self.giaddr
+    }
+
+    /// Client hardware address, all 16 bytes of it. Only the first
+    /// `hlen` bytes are meaningful.
+    pub fn chaddr(&self) -> &'a [u8] {
+        self.chaddr
+    }
+
+    /// Server host name field, as raw bytes
+    pub fn sname(&self) -> &'a [u8] {
+        self.sname
+    }
+
+    /// Boot file name field, as raw bytes
+    pub fn file(&self) -> &'a [u8] {
+        self.file
+    }
+
+    /// How the `sname` and `file` fields are being used, according to
+    /// the Option Overload option (52), if there is one
+    pub fn overload(&self) -> Option<OptionOverloadType> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::OptionOverload(t) => Some(t),
+            _ => None,
+        }).next()
+    }
+
+    /// Whether `sname` holds options rather than a server host name
+    pub fn sname_has_options(&self) -> bool {
+        match self.overload() {
+            Some(OptionOverloadType::Sname) | Some(OptionOverloadType::FileAndSname) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether `file` holds options rather than a boot file name
+    pub fn file_has_options(&self) -> bool {
+        match self.overload() {
+            Some(OptionOverloadType::File) | Some(OptionOverloadType::FileAndSname) => true,
+            _ => false,
+        }
+    }
+
+    /// Options, in the order they appeared on the wire. If the message
+    /// uses option overload, the options from `file` come after the ones
+    /// from the options field, followed by the ones from `sname`.
+    pub fn options(&self) -> &[DhcpOption] {
+        &self.options
+    }
+
+    /// Mutable access to the options, for changing a message before
+    /// encoding it again
+    pub fn options_mut(&mut self) -> &mut Vec<DhcpOption> {
+        &mut self.options
+    }
+
+    /// Set the relay agent hop count
+    pub fn set_hops(&mut self, hops: u8) {
+        self.hops = hops;
+    }
+
+    /// Set the relay agent IP address
+    pub fn set_giaddr(&mut self, giaddr: IpAddr) {
+        self.giaddr = giaddr;
+    }
+
+    /// Encode the message into wire format
+    ///
+    /// This writes the fixed 236-byte header, the magic cookie, and then
+    /// every option followed by an `End`. Parsing the result gives back
+    /// an identical `RawMessage`, unless the message used option
+    /// overload: all options are written to the options field then, so
+    /// the Option Overload option is dropped and whichever of `sname`
+    /// and `file` held options is zeroed.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(300);
+        bytes.push(self.op as u8);
+        bytes.push(self.htype.to_byte());
+        bytes.push(self.hlen);
+        bytes.push(self.hops);
+        bytes.extend(u32_bytes(self.xid).into_iter());
+        bytes.extend(u16_bytes(self.secs).into_iter());
+        bytes.extend(u16_bytes(self.flags).into_iter());
+        for addr in &[self.ciaddr, self.yiaddr, self.siaddr, self.giaddr] {
+            bytes.extend(try!(ip(addr)).into_iter());
+        }
+        try!(encode_fixed(&mut bytes, self.chaddr, 16, "chaddr"));
+        let sname = if self.sname_has_options() { &[][..] } else { self.sname };
+        let file = if self.file_has_options() { &[][..] } else { self.file };
+        try!(encode_fixed(&mut bytes, sname, 64, "sname"));
+        try!(encode_fixed(&mut bytes, file, 128, "file"));
+        bytes.extend(MAGIC_COOKIE.iter().cloned());
+        if self.overload().is_some() {
+            let opts: Vec<_> = self.options.iter().filter(|o| match **o {
+                DhcpOption::OptionOverload(_) => false,
+                _ => true,
+            }).cloned().collect();
+            bytes.extend(try!(options::encode(&opts)).into_iter());
+        } else {
+            bytes.extend(try!(options::encode(&self.options)).into_iter());
+        }
+        Ok(bytes)
+    }
+}
+
+/// Write `field` into a zero-padded area of `width` bytes
+fn encode_fixed(bytes: &mut Vec<u8>, field: &[u8], width: usize, name: &str) -> Result<()> {
+    if field.len() > width {
+        return Err(Error::EncodeError(format!("`{}` is {} bytes long, but only {} fit", name, field.len(), width)));
+    }
+    bytes.extend(field.iter().cloned());
+    bytes.extend((field.len()..width).map(|_| 0u8));
+    Ok(())
+}
+
+/// Offset of the `sname` field
+const SNAME_OFFSET: usize = 44;
+/// Offset of the `file` field
+const FILE_OFFSET: usize = 108;
+/// Offset of the options area, right after the magic cookie
+const OPTIONS_OFFSET: usize = 240;
+
+/// Parse a BOOTP/DHCP packet
+///
+/// `bytes` should be the UDP payload, starting at the `op` field.
+/// Every byte of it has to be accounted for by the fixed header, the
+/// magic cookie, and the options area.
+///
+/// If the options include Option Overload (52), the `file` and/or
+/// `sname` fields are parsed as further options, in that order, as
+/// RFC 2131 section 4.1 describes.
+pub fn parse<'a>(bytes: &'a [u8]) -> Result<RawMessage<'a>> {
+    let (msg, mut errors) = try!(parse_message(bytes, false));
+    match errors.pop() {
+        Some(e) => Err(e),
+        None => Ok(msg),
+    }
+}
+
+/// Parse a BOOTP/DHCP packet, decoding as much of it as possible
+///
+/// The header still has to be intact, but problems in the options
+/// don't fail the parse. Options that can't be decoded are skipped,
+/// an option whose length runs off the end of the packet ends that
+/// options area, and bytes after `End` are ignored. Each of those is
+/// reported in the returned list of errors, which is empty exactly
+/// when `parse` would have succeeded.
+pub fn parse_lenient<'a>(bytes: &'a [u8]) -> Result<(RawMessage<'a>, Vec<Error>)> {
+    parse_message(bytes, true)
+}
+
+fn parse_message<'a>(bytes: &'a [u8], lenient: bool) -> Result<(RawMessage<'a>, Vec<Error>)> {
+    let (mut msg, areas) = try!(split_packet(bytes));
+    let (options, mut errors) = options::parse_areas(&areas);
+    msg.options = options;
+    if !lenient {
+        errors.truncate(1);
+    }
+    Ok((msg, errors))
+}
+
+/// Parses the fixed header, and finds the areas of the packet that
+/// hold options, as `(bytes, offset)` pairs: the options field, and
+/// then `file` and/or `sname` if the Option Overload option in the
+/// options field says so. Options can be split between areas, so they
+/// all have to be parsed together.
+fn split_packet<'a>(bytes: &'a [u8]) -> Result<(RawMessage<'a>, Vec<(&'a [u8], usize)>)> {
+    let (msg, opts) = match _parse_message(bytes) {
+        IResult::Done(_, o) => o,
+        IResult::Error(_) | IResult::Incomplete(_) => {
+            return Err(header_error(bytes));
+        }
+    };
+    let mut areas = vec![(opts, OPTIONS_OFFSET)];
+    let (overload, _) = options::locate_option(&areas, 52u8);
+    let overload = if overload.len() == 1 { OptionOverloadType::from_u8(overload[0]) } else { None };
+    match overload {
+        Some(OptionOverloadType::File) => areas.push((msg.file, FILE_OFFSET)),
+        Some(OptionOverloadType::Sname) => areas.push((msg.sname, SNAME_OFFSET)),
+        Some(OptionOverloadType::FileAndSname) => {
+            areas.push((msg.file, FILE_OFFSET));
+            areas.push((msg.sname, SNAME_OFFSET));
         },
-        IResult::Incomplete(_) => {
-            Err(Error::ParseError("INCOMPLETE".into()))
+        None => {},
+    }
+    Ok((msg, areas))
+}
+
+/// Works out why `_parse_message` rejected `bytes`, checking the
+/// header in the same order it is parsed.
+fn header_error(bytes: &[u8]) -> Error {
+    if bytes.len() > 0 {
+        if let Err(e) = Op::from_byte(bytes[0]) {
+            return e;
         }
     }
+    if bytes.len() < OPTIONS_OFFSET {
+        return Error::TruncatedHeader { offset: bytes.len(), field: header_field(bytes.len()) };
+    }
+    Error::BadMagicCookie { offset: OPTIONS_OFFSET - MAGIC_COOKIE.len() }
+}
+
+/// Name of the header field that byte `offset` falls in
+fn header_field(offset: usize) -> &'static str {
+    match offset {
+        0 => "op",
+        1 => "htype",
+        2 => "hlen",
+        3 => "hops",
+        4...7 => "xid",
+        8...9 => "secs",
+        10...11 => "flags",
+        12...15 => "ciaddr",
+        16...19 => "yiaddr",
+        20...23 => "siaddr",
+        24...27 => "giaddr",
+        28...43 => "chaddr",
+        SNAME_OFFSET...107 => "sname",
+        FILE_OFFSET...235 => "file",
+        _ => "magic cookie",
+    }
 }
 
-named!(_parse_message(&'a [u8]) -> RawMessage<'a>,
+named!(_parse_message(&'a [u8]) -> (RawMessage<'a>, &'a [u8]),
     chain!(
         pop: map_res!(be_u8, Op::from_byte) ~
-        phtype: map_res!(be_u8, Htype::from_byte) ~
+        phtype: map!(be_u8, Htype::from_byte) ~
         phlen: be_u8 ~
         phops: be_u8 ~
         pxid: be_u32 ~
@@ -119,9 +483,9 @@ named!(_parse_message(&'a [u8]) -> RawMessage<'a>,
         psname: take!(64) ~
         pfile: take!(128) ~
         _cookie: tag!(&MAGIC_COOKIE) ~
-        poptions: map_res!(take_rest, options::parse),
+        poptions: take_rest,
     ||{
-        RawMessage {
+        (RawMessage {
             op: pop,
             htype: phtype,
             hlen: phlen,
@@ -136,8 +500,8 @@ named!(_parse_message(&'a [u8]) -> RawMessage<'a>,
             chaddr: pchaddr,
             sname: psname,
             file: pfile,
-            options: poptions,
-        }
+            options: vec![],
+        }, poptions)
     }
     )
 );
@@ -146,7 +510,8 @@ named!(_parse_message(&'a [u8]) -> RawMessage<'a>,
 mod tests {
 
     use std::str;
-    use super::{parse_message, RawMessage};
+    use std::net::{IpAddr, Ipv4Addr};
+    use super::{parse, parse_lenient, Error, RawMessage, DhcpOption, DhcpMessageTypes, OptionOverloadType};
     use super::op::{Op};
     use super::htype::{Htype};
 
@@ -200,7 +565,7 @@ fn test_parse_message() {
 
         99, 130, 83, 99,                        // magic cookie
     ];
-    assert_eq!(parse_message(&test_message).unwrap(), RawMessage {
+    assert_eq!(parse(&test_message).unwrap(), RawMessage {
         op: Op::BootRequest,
         htype: Htype::Experimental_Ethernet_3mb,
         hlen: 3,
@@ -250,4 +615,198 @@ fn test_parse_message() {
 
 }
 
+#[test]
+fn test_accessors() {
+    let mut test_message: Vec<u8> = vec![
+        2u8, 1, 6, 0,                           // op, htype, hlen, hops
+        0, 0, 0, 42,                            // xid
+        0, 3,                                   // secs
+        128, 0,                                 // flags
+        0, 0, 0, 0,                             // ciaddr
+        192, 168, 1, 10,                        // yiaddr
+        192, 168, 1, 1,                         // siaddr
+        0, 0, 0, 0,                             // giaddr
+        0, 17, 34, 51, 68, 85, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0,                 // chaddr
+    ];
+    test_message.extend(vec![0u8; 192].into_iter());   // sname, file
+    test_message.extend(vec![99u8, 130, 83, 99, 53, 1, 5, 255].into_iter());
+
+    let msg = parse(&test_message).unwrap();
+    assert_eq!(msg.op(), Op::BootReply);
+    assert_eq!(msg.htype(), Htype::Ethernet_10mb);
+    assert_eq!(msg.hlen(), 6);
+    assert!(msg.hlen_is_valid());
+    assert_eq!(msg.hops(), 0);
+    assert_eq!(msg.xid(), 42);
+    assert_eq!(msg.secs(), 3);
+    assert_eq!(msg.flags(), 0x8000);
+    assert_eq!(msg.yiaddr(), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)));
+    assert_eq!(msg.siaddr(), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+    assert_eq!(&msg.chaddr()[..6], &[0u8, 17, 34, 51, 68, 85][..]);
+    assert_eq!(msg.sname().len(), 64);
+    assert_eq!(msg.file().len(), 128);
+    assert_eq!(msg.options(), &[DhcpOption::MessageType(DhcpMessageTypes::Ack)][..]);
+
+    test_message[1] = 32;
+    let msg = parse(&test_message).unwrap();
+    assert_eq!(msg.htype(), Htype::InfiniBand);
+    assert!(!msg.hlen_is_valid());
+}
+
+#[test]
+fn test_encode_round_trip() {
+    let mut test_message: Vec<u8> = vec![
+        1u8, 1, 6, 1,                           // op, htype, hlen, hops
+        57, 3, 243, 38,                         // xid
+        0, 0,                                   // secs
+        0, 0,                                   // flags
+        0, 0, 0, 0,                             // ciaddr
+        0, 0, 0, 0,                             // yiaddr
+        0, 0, 0, 0,                             // siaddr
+        10, 0, 0, 1,                            // giaddr
+        0, 17, 34, 51, 68, 85, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0,                 // chaddr
+    ];
+    test_message.extend(vec![0u8; 192].into_iter());   // sname, file
+    test_message.extend(vec![99u8, 130, 83, 99,
+                             53, 1, 1,
+                             55, 3, 1, 3, 6,
+                             255].into_iter());
+
+    let mut msg = parse(&test_message).unwrap();
+    assert_eq!(msg.encode().unwrap(), test_message);
+
+    msg.set_hops(2);
+    msg.set_giaddr(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+    msg.options_mut().push(DhcpOption::HostName("pc".to_owned()));
+    let bytes = msg.encode().unwrap();
+    assert_eq!(bytes[3], 2);
+    assert_eq!(&bytes[24..28], &[10u8, 0, 0, 2][..]);
+    assert_eq!(parse(&bytes).unwrap(), msg);
+}
+
+#[test]
+fn test_option_overload() {
+    let mut test_message: Vec<u8> = vec![
+        2u8, 1, 6, 0,                           // op, htype, hlen, hops
+        0, 0, 0, 1,                             // xid
+        0, 0,                                   // secs
+        0, 0,                                   // flags
+        0, 0, 0, 0,                             // ciaddr
+        10, 0, 0, 5,                            // yiaddr
+        0, 0, 0, 0,                             // siaddr
+        0, 0, 0, 0,                             // giaddr
+        0, 17, 34, 51, 68, 85, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0,                 // chaddr
+    ];
+    let mut sname = vec![12u8, 4, 104, 111, 115, 116, 255];
+    sname.extend(vec![0u8; 57].into_iter());
+    let mut file = vec![15u8, 3, 108, 97, 110, 255];
+    file.extend(vec![0u8; 122].into_iter());
+    test_message.extend(sname.into_iter());
+    test_message.extend(file.into_iter());
+    test_message.extend(vec![99u8, 130, 83, 99,
+                             53, 1, 5,
+                             52, 1, 3,
+                             255].into_iter());
+
+    let msg = parse(&test_message).unwrap();
+    assert!(msg.sname_has_options());
+    assert!(msg.file_has_options());
+    assert_eq!(msg.options(), &[
+        DhcpOption::MessageType(DhcpMessageTypes::Ack),
+        DhcpOption::OptionOverload(OptionOverloadType::FileAndSname),
+        DhcpOption::DomainName("lan".to_owned()),
+        DhcpOption::HostName("host".to_owned()),
+    ][..]);
+
+    let reparsed_bytes = msg.encode().unwrap();
+    let reparsed = parse(&reparsed_bytes).unwrap();
+    assert_eq!(reparsed.overload(), None);
+    assert_eq!(reparsed.sname(), &[0u8; 64][..]);
+    assert_eq!(reparsed.options(), &[
+        DhcpOption::MessageType(DhcpMessageTypes::Ack),
+        DhcpOption::DomainName("lan".to_owned()),
+        DhcpOption::HostName("host".to_owned()),
+    ][..]);
+}
+
+#[test]
+fn test_option_split_between_areas() {
+    let mut test_message: Vec<u8> = vec![2u8, 1, 6, 0];
+    test_message.extend(vec![0u8; 104].into_iter());    // up to and including sname
+    let mut file = vec![3u8, 4, 10, 0, 0, 2, 255];
+    file.extend(vec![0u8; 121].into_iter());
+    test_message.extend(file.into_iter());
+    test_message.extend(vec![99u8, 130, 83, 99,
+                             3, 4, 10, 0, 0, 1,
+                             52, 1, 1,
+                             255].into_iter());
+
+    let msg = parse(&test_message).unwrap();
+    assert_eq!(msg.options(), &[
+        DhcpOption::Router(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]),
+        DhcpOption::OptionOverload(OptionOverloadType::File),
+    ][..]);
+}
+
+#[test]
+fn test_header_errors() {
+    let mut test_message: Vec<u8> = vec![
+        1u8, 1, 6, 0,                           // op, htype, hlen, hops
+        0, 0, 0, 1,                             // xid
+        0, 0,                                   // secs
+        0, 0,                                   // flags
+    ];
+    assert_eq!(parse(&test_message), Err(Error::TruncatedHeader { offset: 12, field: "ciaddr" }));
+    assert_eq!(parse(&[]), Err(Error::TruncatedHeader { offset: 0, field: "op" }));
+    assert_eq!(parse(&[3u8, 1]), Err(Error::InvalidOp { offset: 0, value: 3 }));
+    assert_eq!(parse(&[1u8, 200]), Err(Error::TruncatedHeader { offset: 2, field: "hlen" }));
+
+    test_message.extend(vec![0u8; 224].into_iter());   // addresses, chaddr, sname, file
+    test_message.extend(vec![99u8, 130, 83, 98].into_iter());
+    assert_eq!(parse(&test_message), Err(Error::BadMagicCookie { offset: 236 }));
+
+    test_message[239] = 99;
+    test_message.extend(vec![53u8, 1, 1, 12, 2, 0xc3, 0x28, 255].into_iter());
+    assert_eq!(parse(&test_message), Err(Error::InvalidUtf8 { offset: 243, code: 12 }));
+
+    test_message[245] = 104;
+    test_message.extend(vec![0u8, 1].into_iter());
+    assert_eq!(parse(&test_message), Err(Error::LeftoverInput { offset: 249 }));
+    assert_eq!(parse_lenient(&test_message).unwrap().1, vec![Error::LeftoverInput { offset: 249 }]);
+}
+
+#[test]
+fn test_parse_lenient() {
+    let mut test_message: Vec<u8> = vec![
+        1u8, 1, 6, 0,                           // op, htype, hlen, hops
+        0, 0, 0, 1,                             // xid
+        0, 0,                                   // secs
+        0, 0,                                   // flags
+    ];
+    test_message.extend(vec![0u8; 224].into_iter());   // addresses, chaddr, sname, file
+    test_message.extend(vec![99u8, 130, 83, 99,
+                             53, 1, 3,
+                             50, 3, 10, 0, 0,
+                             12, 2, 112, 99,
+                             255,
+                             0, 0, 7].into_iter());
+
+    let (msg, errors) = parse_lenient(&test_message).unwrap();
+    assert_eq!(msg.options(), &[
+        DhcpOption::MessageType(DhcpMessageTypes::Request),
+        DhcpOption::HostName("pc".to_owned()),
+    ][..]);
+    assert_eq!(errors, vec![
+        Error::OptionLengthMismatch { offset: 243, code: 50 },
+        Error::LeftoverInput { offset: 255 },
+    ]);
+    assert_eq!(parse(&test_message), Err(Error::OptionLengthMismatch { offset: 243, code: 50 }));
+
+    assert_eq!(parse_lenient(&test_message[..100]).unwrap_err(),
+               Error::TruncatedHeader { offset: 100, field: "sname" });
+}
+
 }
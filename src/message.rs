@@ -0,0 +1,360 @@
+use std::fmt;
+use std::net::{IpAddr};
+
+use {Result, Error, RawMessage, SNAME_OFFSET, FILE_OFFSET};
+use op::{Op};
+use htype::{Htype};
+use options::{DhcpOption, DhcpMessageTypes, ClientArchitecture, PxeSubOption, pxe, MIN_V6ONLY_WAIT};
+use util::{null_terminated_slice_to_string};
+
+/// A client hardware address, already cut down to `hlen` bytes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HardwareAddress(pub Vec<u8>);
+
+impl HardwareAddress {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for HardwareAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f, ":"));
+            }
+            try!(write!(f, "{:02x}", b));
+        }
+        Ok(())
+    }
+}
+
+/// An owned, higher-level version of `RawMessage`
+///
+/// `chaddr` only holds the `hlen` bytes that are actually used, and
+/// `sname` and `file` are strings. They are `None` when the field is
+/// empty, or when it was used to hold options (see option 52); in the
+/// latter case the options it held are in `options` with the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub op: Op,
+    pub htype: Htype,
+    pub hops: u8,
+    pub xid: u32,
+    pub secs: u16,
+    pub flags: u16,
+    pub ciaddr: IpAddr,
+    pub yiaddr: IpAddr,
+    pub siaddr: IpAddr,
+    pub giaddr: IpAddr,
+    pub chaddr: HardwareAddress,
+    pub sname: Option<String>,
+    pub file: Option<String>,
+    pub options: Vec<DhcpOption>,
+}
+
+impl Message {
+    /// Build a `Message` out of a parsed `RawMessage`
+    ///
+    /// This fails if `sname` or `file` hold a name that isn't a
+    /// null-terminated UTF-8 string. The Option Overload option, if
+    /// any, is left out of `options`, since it only describes how the
+    /// raw message was laid out.
+    pub fn from_raw(raw: &RawMessage) -> Result<Message> {
+        let sname = if raw.sname_has_options() {
+            None
+        } else {
+            try!(string_field(raw.sname, SNAME_OFFSET, "sname"))
+        };
+        let file = if raw.file_has_options() {
+            None
+        } else {
+            try!(string_field(raw.file, FILE_OFFSET, "file"))
+        };
+        let hlen = ::std::cmp::min(raw.hlen as usize, raw.chaddr.len());
+        Ok(Message {
+            op: raw.op,
+            htype: raw.htype,
+            hops: raw.hops,
+            xid: raw.xid,
+            secs: raw.secs,
+            flags: raw.flags,
+            ciaddr: raw.ciaddr,
+            yiaddr: raw.yiaddr,
+            siaddr: raw.siaddr,
+            giaddr: raw.giaddr,
+            chaddr: HardwareAddress(raw.chaddr[..hlen].to_vec()),
+            sname: sname,
+            file: file,
+            options: without_overload(&raw.options),
+        })
+    }
+
+    /// Encode the message into wire format
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.chaddr.0.len() > 16 {
+            return Err(Error::EncodeError(format!("`chaddr` is {} bytes long, but only 16 fit", self.chaddr.0.len())));
+        }
+        // leave room for the null terminator
+        if self.sname.as_ref().map_or(false, |s| s.len() >= 64) {
+            return Err(Error::EncodeError("`sname` must be shorter than 64 bytes".to_owned()));
+        }
+        if self.file.as_ref().map_or(false, |s| s.len() >= 128) {
+            return Err(Error::EncodeError("`file` must be shorter than 128 bytes".to_owned()));
+        }
+        RawMessage::from(self).encode()
+    }
+
+    /// The DHCP message type, from option 53
+    pub fn message_type(&self) -> Option<DhcpMessageTypes> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::MessageType(t) => Some(t),
+            _ => None,
+        }).next()
+    }
+
+    /// IP address lease time in seconds, from option 51
+    pub fn lease_time(&self) -> Option<u32> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::IpAddressLeaseTime(t) => Some(t),
+            _ => None,
+        }).next()
+    }
+
+    /// Server identifier, from option 54
+    pub fn server_identifier(&self) -> Option<IpAddr> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::ServerIdentifier(a) => Some(a),
+            _ => None,
+        }).next()
+    }
+
+    /// Requested IP address, from option 50
+    pub fn requested_ip(&self) -> Option<IpAddr> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::RequestedIpAddress(a) => Some(a),
+            _ => None,
+        }).next()
+    }
+
+    /// Routers, in order of preference, from option 3
+    pub fn routers(&self) -> Option<&[IpAddr]> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::Router(ref a) => Some(&a[..]),
+            _ => None,
+        }).next()
+    }
+
+    /// Domain name servers, in order of preference, from option 6
+    pub fn dns_servers(&self) -> Option<&[IpAddr]> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::DomainNameServer(ref a) => Some(&a[..]),
+            _ => None,
+        }).next()
+    }
+
+    /// Whether option `code` is in the Parameter Request List (option 55)
+    pub fn requests_option(&self, code: u8) -> bool {
+        self.options.iter().any(|o| match *o {
+            DhcpOption::ParamRequestList(ref codes) => codes.contains(&code),
+            _ => false,
+        })
+    }
+
+    /// Whether the client asked for IPv6-Only Preferred (option 108).
+    /// RFC 8925 only lets a server send it to clients that did.
+    pub fn requests_ipv6_only_preferred(&self) -> bool {
+        self.requests_option(108)
+    }
+
+    /// How long to stop using IPv4 for, from option 108, after raising
+    /// it to `MIN_V6ONLY_WAIT` as RFC 8925 asks clients to
+    pub fn ipv6_only_wait(&self) -> Option<u32> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::Ipv6OnlyPreferred(t) => Some(::std::cmp::max(t, MIN_V6ONLY_WAIT)),
+            _ => None,
+        }).next()
+    }
+
+    /// Client system architectures, in order of preference, from option 93
+    pub fn client_architectures(&self) -> Option<&[ClientArchitecture]> {
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::ClientSystemArchitecture(ref a) => Some(&a[..]),
+            _ => None,
+        }).next()
+    }
+
+    /// PXE sub-options, from option 43
+    ///
+    /// The contents of option 43 are up to the vendor, so this is `None`
+    /// unless option 60 starts with "PXEClient", as well as when option
+    /// 43 is missing or doesn't hold valid PXE sub-options.
+    pub fn pxe_options(&self) -> Option<Vec<PxeSubOption>> {
+        let is_pxe = self.options.iter().any(|o| match *o {
+            DhcpOption::ClassIdentifier(ref c) => c.starts_with(b"PXEClient"),
+            _ => false,
+        });
+        if !is_pxe {
+            return None;
+        }
+        self.options.iter().filter_map(|o| match *o {
+            DhcpOption::VendorExtensions(ref v) => pxe::parse(v),
+            _ => None,
+        }).next()
+    }
+}
+
+/// Any Option Overload in `msg.options` is left out, since `sname` and
+/// `file` always hold the message's own strings
+impl<'a> From<&'a Message> for RawMessage<'a> {
+    fn from(msg: &'a Message) -> RawMessage<'a> {
+        RawMessage {
+            op: msg.op,
+            htype: msg.htype,
+            hlen: msg.chaddr.0.len() as u8,
+            hops: msg.hops,
+            xid: msg.xid,
+            secs: msg.secs,
+            flags: msg.flags,
+            ciaddr: msg.ciaddr,
+            yiaddr: msg.yiaddr,
+            siaddr: msg.siaddr,
+            giaddr: msg.giaddr,
+            chaddr: &msg.chaddr.0,
+            sname: msg.sname.as_ref().map_or(&[][..], |s| s.as_bytes()),
+            file: msg.file.as_ref().map_or(&[][..], |s| s.as_bytes()),
+            options: without_overload(&msg.options),
+        }
+    }
+}
+
+fn without_overload(options: &[DhcpOption]) -> Vec<DhcpOption> {
+    options.iter().filter(|o| match **o {
+        DhcpOption::OptionOverload(_) => false,
+        _ => true,
+    }).cloned().collect()
+}
+
+/// `None` for an empty field, otherwise the string up to the first null
+fn string_field(bytes: &[u8], offset: usize, field: &'static str) -> Result<Option<String>> {
+    let s = try!(null_terminated_slice_to_string(bytes, offset, field));
+    if s.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(s.to_owned()))
+    }
+}
+
+#[cfg(test)] mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use super::{Message, HardwareAddress};
+    use {parse, Error, RawMessage};
+    use op::{Op};
+    use htype::{Htype};
+    use options::{DhcpOption, DhcpMessageTypes, OptionOverloadType, ClientArchitecture, PxeSubOption};
+
+    fn ack() -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![
+            2u8, 1, 6, 0,                           // op, htype, hlen, hops
+            0, 0, 0, 7,                             // xid
+            0, 0,                                   // secs
+            0, 0,                                   // flags
+            0, 0, 0, 0,                             // ciaddr
+            192, 168, 1, 10,                        // yiaddr
+            0, 0, 0, 0,                             // siaddr
+            0, 0, 0, 0,                             // giaddr
+            0, 17, 34, 51, 68, 85, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,                 // chaddr
+        ];
+        bytes.extend(vec![0u8; 64].into_iter());    // sname
+        bytes.extend(b"pxelinux.0".iter().cloned());
+        bytes.extend(vec![0u8; 118].into_iter());   // file
+        bytes.extend(vec![99u8, 130, 83, 99,
+                          53, 1, 5,
+                          54, 4, 192, 168, 1, 1,
+                          51, 4, 0, 0, 14, 16,
+                          3, 4, 192, 168, 1, 1,
+                          6, 8, 8, 8, 8, 8, 8, 8, 4, 4,
+                          255].into_iter());
+        bytes
+    }
+
+    #[test]
+    fn test_from_raw() {
+        let bytes = ack();
+        let msg = Message::from_raw(&parse(&bytes).unwrap()).unwrap();
+        assert_eq!(msg.op, Op::BootReply);
+        assert_eq!(msg.htype, Htype::Ethernet_10mb);
+        assert_eq!(msg.chaddr, HardwareAddress(vec![0, 17, 34, 51, 68, 85]));
+        assert_eq!(msg.chaddr.to_string(), "00:11:22:33:44:55");
+        assert_eq!(msg.sname, None);
+        assert_eq!(msg.file, Some("pxelinux.0".to_owned()));
+        assert_eq!(msg.message_type(), Some(DhcpMessageTypes::Ack));
+        assert_eq!(msg.lease_time(), Some(3600));
+        assert_eq!(msg.server_identifier(), Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))));
+        assert_eq!(msg.requested_ip(), None);
+        assert_eq!(msg.routers(), Some(&[IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))][..]));
+        assert_eq!(msg.dns_servers(), Some(&[IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+                                             IpAddr::V4(Ipv4Addr::new(8, 8, 4, 4))][..]));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = ack();
+        let msg = Message::from_raw(&parse(&bytes).unwrap()).unwrap();
+        assert_eq!(msg.encode().unwrap(), bytes);
+
+        let raw = RawMessage::from(&msg);
+        assert_eq!(raw.hlen(), 6);
+        assert_eq!(raw.file(), &b"pxelinux.0"[..]);
+        assert_eq!(raw.options(), &msg.options[..]);
+    }
+
+    #[test]
+    fn test_overload_is_dropped() {
+        let mut msg = Message::from_raw(&parse(&ack()).unwrap()).unwrap();
+        msg.options.push(DhcpOption::OptionOverload(OptionOverloadType::FileAndSname));
+        msg.sname = Some("boot.test".to_owned());
+        let bytes = msg.encode().unwrap();
+        let msg = Message::from_raw(&parse(&bytes).unwrap()).unwrap();
+        assert_eq!(msg.options.len(), 5);
+        // and doesn't blank the fields it would have covered
+        assert_eq!(msg.sname, Some("boot.test".to_owned()));
+        assert_eq!(msg.file, Some("pxelinux.0".to_owned()));
+    }
+
+    #[test]
+    fn test_ipv6_only_preferred() {
+        let mut msg = Message::from_raw(&parse(&ack()).unwrap()).unwrap();
+        assert!(!msg.requests_ipv6_only_preferred());
+        assert_eq!(msg.ipv6_only_wait(), None);
+        msg.options.push(DhcpOption::ParamRequestList(vec![1, 3, 108]));
+        msg.options.push(DhcpOption::Ipv6OnlyPreferred(60));
+        assert!(msg.requests_ipv6_only_preferred());
+        assert!(msg.requests_option(3));
+        assert_eq!(msg.ipv6_only_wait(), Some(300));
+    }
+
+    #[test]
+    fn test_pxe() {
+        let mut msg = Message::from_raw(&parse(&ack()).unwrap()).unwrap();
+        msg.options.push(DhcpOption::VendorExtensions(vec![6u8, 1, 8, 255]));
+        assert_eq!(msg.pxe_options(), None);
+        msg.options.push(DhcpOption::ClassIdentifier(b"PXEClient:Arch:00007".to_vec()));
+        msg.options.push(DhcpOption::ClientSystemArchitecture(vec![ClientArchitecture::X64Uefi]));
+        assert_eq!(msg.pxe_options(), Some(vec![PxeSubOption::DiscoveryControl(8)]));
+        assert_eq!(msg.client_architectures(), Some(&[ClientArchitecture::X64Uefi][..]));
+    }
+
+    #[test]
+    fn test_bad_strings() {
+        let mut bytes = ack();
+        bytes[108] = 0xff;
+        assert_eq!(Message::from_raw(&parse(&bytes).unwrap()),
+                   Err(Error::InvalidString { offset: 108, field: "file" }));
+
+        let mut msg = Message::from_raw(&parse(&ack()).unwrap()).unwrap();
+        msg.sname = Some(String::from_utf8(vec![b'a'; 64]).unwrap());
+        assert!(msg.encode().is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use super::{Error, Result};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Op {
     BootRequest = 1,
     BootReply,
@@ -11,7 +11,7 @@ impl Op {
         match byte {
             1u8 => { Ok(Op::BootRequest) },
             2u8 => { Ok(Op::BootReply) },
-            _ => { Err(Error::ParseError("Got bad value for `op`".into())) }
+            _ => { Err(Error::InvalidOp { offset: 0, value: byte }) }
         }
     }
 }
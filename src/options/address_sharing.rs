@@ -0,0 +1,209 @@
+//! Options for IPv6 transition with a shared IPv4 address: 6rd (option
+//! 212, RFC 5969) and Port Parameters (option 159, RFC 7618)
+
+use {Result, Error};
+use util::{ip};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// 6rd configuration (RFC 5969)
+#[derive(Debug, PartialEq, Clone)]
+pub struct SixRd {
+    /// High bits of the CE's IPv4 address that every CE in the domain
+    /// shares, and so are left out of the delegated prefix
+    pub ipv4_mask_len: u8,
+    pub prefix_len: u8,
+    pub prefix: Ipv6Addr,
+    pub border_relays: Vec<IpAddr>,
+}
+
+impl SixRd {
+    /// Derives the IPv6 prefix delegated to a CE with the address
+    /// `client`: the 6rd prefix followed by the low `32 -
+    /// ipv4_mask_len` bits of the address. Returns the prefix and its
+    /// length, or `None` if `client` isn't IPv4 or the result would be
+    /// longer than 128 bits.
+    pub fn delegated_prefix(&self, client: &IpAddr) -> Option<(Ipv6Addr, u8)> {
+        let client = match *client {
+            IpAddr::V4(a) => u32::from(a),
+            IpAddr::V6(_) => return None,
+        };
+        if self.ipv4_mask_len > 32 || self.prefix_len as u32 + 32 - self.ipv4_mask_len as u32 > 128 {
+            return None;
+        }
+        let suffix_len = 32 - self.ipv4_mask_len as u32;
+        let len = self.prefix_len as u32 + suffix_len;
+        let mut prefix = u128::from(self.prefix) & high_bits(self.prefix_len as u32);
+        if suffix_len > 0 {
+            let suffix = (client as u64 & ((1u64 << suffix_len) - 1)) as u128;
+            prefix |= suffix << (128 - len);
+        }
+        Some((Ipv6Addr::from(prefix), len as u8))
+    }
+}
+
+fn high_bits(n: u32) -> u128 {
+    if n == 0 { 0 } else { !0u128 << (128 - n) }
+}
+
+/// `[ipv4-mask-len, 6rd-prefix-len, 6rd-prefix(16), br-address(4)...]`
+pub fn parse_six_rd(bytes: &[u8]) -> Option<SixRd> {
+    if bytes.len() < 22 || (bytes.len() - 18) % 4 != 0 {
+        return None;
+    }
+    let (mask_len, prefix_len) = (bytes[0], bytes[1]);
+    if mask_len > 32 || prefix_len > 128 || prefix_len as u32 + 32 - mask_len as u32 > 128 {
+        return None;
+    }
+    let mut prefix = [0u8; 16];
+    prefix.copy_from_slice(&bytes[2..18]);
+    Some(SixRd {
+        ipv4_mask_len: mask_len,
+        prefix_len: prefix_len,
+        prefix: Ipv6Addr::from(prefix),
+        border_relays: bytes[18..].chunks(4)
+                                  .map(|a| IpAddr::V4(Ipv4Addr::new(a[0], a[1], a[2], a[3])))
+                                  .collect(),
+    })
+}
+
+/// Inverse of `parse_six_rd`
+pub fn encode_six_rd(six_rd: &SixRd) -> Result<Vec<u8>> {
+    if six_rd.border_relays.is_empty() {
+        return Err(Error::EncodeError("6rd needs at least one border relay".to_owned()));
+    }
+    let mut bytes = vec![six_rd.ipv4_mask_len, six_rd.prefix_len];
+    bytes.extend(six_rd.prefix.octets().iter().cloned());
+    for relay in &six_rd.border_relays {
+        bytes.extend(try!(ip(relay)).into_iter());
+    }
+    Ok(bytes)
+}
+
+/// The set of ports a CE may use on a shared IPv4 address (RFC 7618)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PortParameters {
+    /// Number of high bits of a port that can't be used to tell port
+    /// sets apart, which keeps the well-known ports out of every set
+    pub offset: u8,
+    pub psid_len: u8,
+    /// The Port Set ID, right-aligned (on the wire it's left-aligned)
+    pub psid: u16,
+}
+
+impl PortParameters {
+    /// The port ranges in the set, as inclusive `(first, last)` pairs
+    /// (RFC 7597, section 5.1). Ports whose `offset` high bits are all
+    /// zero are never included, unless the offset is zero.
+    pub fn port_ranges(&self) -> Vec<(u16, u16)> {
+        let (a, k) = (self.offset as u32, self.psid_len as u32);
+        if a + k > 16 {
+            return vec![];
+        }
+        let m = 16 - a - k;
+        let first_block = if a == 0 { 0 } else { 1 };
+        (first_block..1u32 << a).map(|block| {
+            let start = block << (16 - a) | (self.psid as u32) << m;
+            (start as u16, (start + (1 << m) - 1) as u16)
+        }).collect()
+    }
+}
+
+/// `[offset, psid-len, psid(2)]`. The PSID's padding bits have to be
+/// zero.
+pub fn parse_port_parameters(bytes: &[u8]) -> Option<PortParameters> {
+    if bytes.len() != 4 {
+        return None;
+    }
+    let (offset, psid_len) = (bytes[0], bytes[1]);
+    let psid = (bytes[2] as u16) << 8 | bytes[3] as u16;
+    if offset > 15 || offset as u32 + psid_len as u32 > 16 {
+        return None;
+    }
+    if psid_len == 0 {
+        return if psid == 0 { Some(PortParameters { offset: offset, psid_len: 0, psid: 0 }) } else { None };
+    }
+    let shift = 16 - psid_len as u32;
+    if psid as u32 & ((1 << shift) - 1) != 0 {
+        return None;
+    }
+    Some(PortParameters { offset: offset, psid_len: psid_len, psid: (psid as u32 >> shift) as u16 })
+}
+
+/// Inverse of `parse_port_parameters`
+pub fn encode_port_parameters(params: &PortParameters) -> Result<Vec<u8>> {
+    if params.offset > 15 || params.offset as u32 + params.psid_len as u32 > 16 ||
+       params.psid as u32 >= 1 << params.psid_len {
+        return Err(Error::EncodeError(format!("Invalid port parameters {:?}", params)));
+    }
+    let psid = if params.psid_len == 0 { 0 } else { (params.psid as u32) << (16 - params.psid_len as u32) };
+    Ok(vec![params.offset, params.psid_len, (psid >> 8) as u8, psid as u8])
+}
+
+#[cfg(test)] mod tests {
+    use super::{SixRd, PortParameters, parse_six_rd, encode_six_rd, parse_port_parameters, encode_port_parameters};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_six_rd() {
+        let bytes = vec![8u8, 32, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                         192, 0, 2, 1];
+        let six_rd = parse_six_rd(&bytes).unwrap();
+        assert_eq!(six_rd, SixRd {
+            ipv4_mask_len: 8,
+            prefix_len: 32,
+            prefix: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0),
+            border_relays: vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))],
+        });
+        assert_eq!(encode_six_rd(&six_rd).unwrap(), bytes);
+
+        // the low 24 bits of 10.100.200.5 follow the 32-bit prefix
+        let client = IpAddr::V4(Ipv4Addr::new(10, 100, 200, 5));
+        assert_eq!(six_rd.delegated_prefix(&client),
+                   Some((Ipv6Addr::new(0x2001, 0xdb8, 0x64c8, 0x0500, 0, 0, 0, 0), 56)));
+
+        let whole = SixRd { ipv4_mask_len: 0, prefix_len: 28, ..six_rd.clone() };
+        assert_eq!(whole.delegated_prefix(&client),
+                   Some((Ipv6Addr::new(0x2001, 0xdb0, 0xa64c, 0x8050, 0, 0, 0, 0), 60)));
+        let none = SixRd { ipv4_mask_len: 32, ..six_rd.clone() };
+        assert_eq!(none.delegated_prefix(&client), Some((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32)));
+        let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        assert_eq!(six_rd.delegated_prefix(&v6), None);
+    }
+
+    #[test]
+    fn test_six_rd_malformed() {
+        let mut bytes = vec![8u8, 32];
+        bytes.extend(vec![0u8; 16].into_iter());
+        assert_eq!(parse_six_rd(&bytes), None);
+        bytes.extend(vec![192u8, 0, 2].into_iter());
+        assert_eq!(parse_six_rd(&bytes), None);
+        bytes.push(1);
+        bytes[1] = 120;
+        assert_eq!(parse_six_rd(&bytes), None);
+        bytes[0] = 33;
+        assert_eq!(parse_six_rd(&bytes), None);
+    }
+
+    #[test]
+    fn test_port_parameters() {
+        let params = parse_port_parameters(&[6u8, 8, 0x34, 0]).unwrap();
+        assert_eq!(params, PortParameters { offset: 6, psid_len: 8, psid: 0x34 });
+        assert_eq!(encode_port_parameters(&params).unwrap(), vec![6u8, 8, 0x34, 0]);
+        let ranges = params.port_ranges();
+        assert_eq!(ranges.len(), 63);
+        assert_eq!(ranges[0], (0x04d0, 0x04d3));
+        assert_eq!(ranges[62], (0xfcd0, 0xfcd3));
+
+        assert_eq!(PortParameters { offset: 0, psid_len: 0, psid: 0 }.port_ranges(), vec![(0, 65535)]);
+        assert_eq!(PortParameters { offset: 0, psid_len: 2, psid: 3 }.port_ranges(), vec![(0xc000, 0xffff)]);
+    }
+
+    #[test]
+    fn test_port_parameters_malformed() {
+        assert_eq!(parse_port_parameters(&[6u8, 8, 0x34]), None);
+        assert_eq!(parse_port_parameters(&[16u8, 0, 0, 0]), None);
+        assert_eq!(parse_port_parameters(&[6u8, 11, 0, 0]), None);
+        assert_eq!(parse_port_parameters(&[6u8, 8, 0x34, 1]), None);
+        assert!(encode_port_parameters(&PortParameters { offset: 6, psid_len: 2, psid: 4 }).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! Authentication (option 90, RFC 3118), and the HMAC-MD5 used by its
+//! delayed authentication protocol and by the reconfigure key protocol
+//! of RFC 3203
+
+use md5;
+
+use {Result, Error, split_packet};
+use options::{locate_option};
+use util::{u32_bytes};
+
+/// Protocol 0: a plain-text token shared by client and server
+pub const PROTOCOL_CONFIGURATION_TOKEN: u8 = 0;
+/// Protocol 1: delayed authentication
+pub const PROTOCOL_DELAYED: u8 = 1;
+/// Protocol 3: reconfigure key, for FORCERENEW (RFC 3203)
+pub const PROTOCOL_RECONFIGURE_KEY: u8 = 3;
+
+/// The only algorithm defined for protocols 1 and 3
+pub const ALGORITHM_HMAC_MD5: u8 = 1;
+
+/// Replay detection method 0: a monotonically increasing counter
+pub const RDM_MONOTONIC: u8 = 0;
+
+/// Reconfigure key `info` type holding the key itself
+pub const RECONFIGURE_KEY_VALUE: u8 = 1;
+/// Reconfigure key `info` type holding an HMAC-MD5 digest
+pub const RECONFIGURE_KEY_HMAC_MD5: u8 = 2;
+
+/// The contents of the Authentication option
+#[derive(Debug, PartialEq, Clone)]
+pub struct Authentication {
+    pub protocol: u8,
+    pub algorithm: u8,
+    /// Replay detection method
+    pub rdm: u8,
+    pub replay_detection: u64,
+    /// Protocol-specific authentication information
+    ///
+    /// For delayed authentication this is empty in a DHCPDISCOVER, and
+    /// otherwise a 4 byte secret ID followed by the 16 byte MAC. For
+    /// the reconfigure key protocol it is a type byte followed by 16
+    /// bytes of key or MAC.
+    pub info: Vec<u8>,
+}
+
+impl Authentication {
+    /// The part of `info` that holds an HMAC-MD5, if this is a protocol
+    /// and type of message that has one
+    fn mac_range(&self) -> Option<(usize, usize)> {
+        if self.algorithm != ALGORITHM_HMAC_MD5 {
+            return None;
+        }
+        match self.protocol {
+            PROTOCOL_DELAYED if self.info.len() == 20 => Some((4, 20)),
+            PROTOCOL_RECONFIGURE_KEY if self.info.len() == 17 && self.info[0] == RECONFIGURE_KEY_HMAC_MD5 => Some((1, 17)),
+            _ => None,
+        }
+    }
+
+    /// The HMAC-MD5 this option carries, if it has one
+    pub fn mac(&self) -> Option<&[u8]> {
+        self.mac_range().map(|(start, end)| &self.info[start..end])
+    }
+
+    /// The secret ID of a delayed authentication option, if it has one
+    pub fn secret_id(&self) -> Option<u32> {
+        if self.protocol != PROTOCOL_DELAYED || self.info.len() < 4 {
+            return None;
+        }
+        Some(self.info[..4].iter().fold(0u32, |acc, b| (acc << 8) | *b as u32))
+    }
+}
+
+/// Decodes an option 90 payload
+pub fn parse(bytes: &[u8]) -> Option<Authentication> {
+    if bytes.len() < 11 {
+        return None;
+    }
+    Some(Authentication {
+        protocol: bytes[0],
+        algorithm: bytes[1],
+        rdm: bytes[2],
+        replay_detection: bytes[3..11].iter().fold(0u64, |acc, b| (acc << 8) | *b as u64),
+        info: bytes[11..].to_vec(),
+    })
+}
+
+/// Inverse of `parse`
+pub fn encode(auth: &Authentication) -> Vec<u8> {
+    let mut bytes = vec![auth.protocol, auth.algorithm, auth.rdm];
+    bytes.extend(u32_bytes((auth.replay_detection >> 32) as u32).into_iter());
+    bytes.extend(u32_bytes(auth.replay_detection as u32).into_iter());
+    bytes.extend(auth.info.iter().cloned());
+    bytes
+}
+
+/// Offsets of the header fields that delayed authentication leaves
+/// out of the MAC, since relay agents change them
+const HOPS_OFFSET: usize = 3;
+const GIADDR_OFFSET: usize = 24;
+
+/// Finds the wire offsets of the MAC in the Authentication option of
+/// `packet`, and whether it uses delayed authentication
+fn mac_offsets(packet: &[u8]) -> Result<(Vec<usize>, bool)> {
+    let (_, areas) = try!(split_packet(packet));
+    let (data, offsets) = locate_option(&areas, 90u8);
+    if offsets.is_empty() {
+        return Err(Error::MissingOption { code: 90 });
+    }
+    // offset of the first instance of the option, for errors
+    let no_mac = Error::InvalidOptionValue { offset: offsets[0] - 2, code: 90 };
+    let auth = try!(parse(&data).ok_or(no_mac.clone()));
+    let (start, end) = try!(auth.mac_range().ok_or(no_mac));
+    // `info` starts 11 bytes into the option's data
+    Ok((offsets[11 + start..11 + end].to_vec(), auth.protocol == PROTOCOL_DELAYED))
+}
+
+/// Computes the HMAC-MD5 that belongs in the Authentication option of
+/// `packet`, using `key`
+///
+/// The MAC covers the packet exactly as it is on the wire, with the MAC
+/// itself zeroed. For delayed authentication, `hops` and `giaddr` are
+/// zeroed as well. This fails if the header doesn't parse, with
+/// `MissingOption` if there is no Authentication option, and with
+/// `InvalidOptionValue` if it has no room for an HMAC-MD5 MAC.
+pub fn compute_hmac_md5(packet: &[u8], key: &[u8]) -> Result<[u8; 16]> {
+    let (offsets, delayed) = try!(mac_offsets(packet));
+    let mut bytes = packet.to_vec();
+    for &i in &offsets {
+        bytes[i] = 0;
+    }
+    if delayed {
+        bytes[HOPS_OFFSET] = 0;
+        for b in &mut bytes[GIADDR_OFFSET..GIADDR_OFFSET + 4] {
+            *b = 0;
+        }
+    }
+    Ok(hmac_md5(key, &bytes))
+}
+
+/// Fills in the MAC of the Authentication option in `packet`, as
+/// computed by `compute_hmac_md5`
+pub fn sign(packet: &mut [u8], key: &[u8]) -> Result<()> {
+    let mac = try!(compute_hmac_md5(packet, key));
+    let (offsets, _) = try!(mac_offsets(packet));
+    for (&i, b) in offsets.iter().zip(mac.iter()) {
+        packet[i] = *b;
+    }
+    Ok(())
+}
+
+/// Whether `packet` carries an Authentication option whose MAC matches
+/// the one computed with `key`
+pub fn verify_hmac_md5(packet: &[u8], key: &[u8]) -> bool {
+    let expected = match compute_hmac_md5(packet, key) {
+        Ok(mac) => mac,
+        Err(_) => return false,
+    };
+    let (offsets, _) = match mac_offsets(packet) {
+        Ok(o) => o,
+        Err(_) => return false,
+    };
+    // compare every byte, so the time taken doesn't give away where the
+    // first difference is
+    offsets.iter().zip(expected.iter()).fold(0u8, |acc, (&i, b)| acc | (packet[i] ^ b)) == 0
+}
+
+/// HMAC (RFC 2104) using MD5
+pub fn hmac_md5(key: &[u8], data: &[u8]) -> [u8; 16] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..16].copy_from_slice(&md5::compute(key).0);
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    inner.extend(data.iter().cloned());
+    let mut outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend(md5::compute(&inner).0.iter().cloned());
+    md5::compute(&outer).0
+}
+
+#[cfg(test)] mod tests {
+    use super::{parse, encode, hmac_md5, compute_hmac_md5, sign, verify_hmac_md5, Authentication};
+    use super::{PROTOCOL_DELAYED, PROTOCOL_RECONFIGURE_KEY, ALGORITHM_HMAC_MD5, RDM_MONOTONIC};
+    use {parse as parse_message, Error};
+    use options::{DhcpOption};
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = vec![1u8, 1, 0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 9];
+        let auth = parse(&bytes).unwrap();
+        assert_eq!(auth.replay_detection, 5);
+        assert_eq!(auth.secret_id(), Some(9));
+        assert_eq!(auth.mac(), None);
+        assert_eq!(encode(&auth), bytes);
+        assert_eq!(parse(&bytes[..10]), None);
+    }
+
+    #[test]
+    fn test_hmac_md5() {
+        // RFC 2202, test cases 1 and 2
+        assert_eq!(hmac_md5(&[0x0bu8; 16], b"Hi There"),
+                   [0x92u8, 0x94, 0x72, 0x7a, 0x36, 0x38, 0xbb, 0x1c, 0x13, 0xf4, 0x8e, 0xf8, 0x15, 0x8b, 0xfc, 0x9d]);
+        assert_eq!(hmac_md5(b"Jefe", b"what do ya want for nothing?"),
+                   [0x75u8, 0x0c, 0x78, 0x3e, 0x6a, 0xb0, 0xb5, 0x03, 0xea, 0xa8, 0x6e, 0x31, 0x0a, 0x5d, 0xb7, 0x38]);
+    }
+
+    fn message() -> Vec<u8> {
+        let mut bytes = vec![2u8, 1, 6, 0, 0, 0, 0, 7];
+        bytes.extend(vec![0u8; 228].into_iter());
+        bytes.extend(vec![99u8, 130, 83, 99, 53, 1, 5, 255].into_iter());
+        bytes
+    }
+
+    /// `message()` with an Authentication option, as it goes on the wire
+    fn signed_message(auth: Authentication) -> Vec<u8> {
+        let bytes = message();
+        let mut msg = parse_message(&bytes).unwrap();
+        msg.options_mut().push(DhcpOption::Authentication(auth));
+        msg.encode().unwrap()
+    }
+
+    #[test]
+    fn test_delayed_authentication() {
+        let mut info = vec![0u8, 0, 0, 42];
+        info.extend(vec![0u8; 16].into_iter());
+        let mut bytes = signed_message(Authentication {
+            protocol: PROTOCOL_DELAYED,
+            algorithm: ALGORITHM_HMAC_MD5,
+            rdm: RDM_MONOTONIC,
+            replay_detection: 1,
+            info: info,
+        });
+        assert!(!verify_hmac_md5(&bytes, b"key"));
+        sign(&mut bytes, b"key").unwrap();
+        assert!(verify_hmac_md5(&bytes, b"key"));
+        assert!(!verify_hmac_md5(&bytes, b"other key"));
+        match parse_message(&bytes).unwrap().options()[1] {
+            DhcpOption::Authentication(ref a) => assert_eq!(a.mac().unwrap(), &compute_hmac_md5(&bytes, b"key").unwrap()[..]),
+            ref o => panic!("Unexpected option {:?}", o),
+        }
+
+        // relay agents can change these without breaking the MAC
+        bytes[3] = 1;
+        bytes[24] = 10;
+        bytes[27] = 1;
+        assert!(verify_hmac_md5(&bytes, b"key"));
+        bytes[4] = 1;
+        assert!(!verify_hmac_md5(&bytes, b"key"));
+    }
+
+    #[test]
+    fn test_captured_packet() {
+        // a relayed DHCPREQUEST with Pad before the Authentication option
+        // and padding after End, which re-encoding would drop
+        let mut bytes = vec![1u8, 1, 6, 1, 0x12, 0x34, 0x56, 0x78, 0, 0, 0, 0];
+        bytes.extend(vec![0u8; 12].into_iter());
+        bytes.extend(vec![10u8, 0, 0, 1].into_iter());
+        bytes.extend(vec![0u8; 208].into_iter());
+        bytes.extend(vec![99u8, 130, 83, 99, 53, 1, 3, 0, 0,
+                          90, 31, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 42].into_iter());
+        let mac = [0xc8u8, 0x26, 0x6a, 0xf0, 0x23, 0xa7, 0x90, 0xd8, 0xbe, 0x22, 0x96, 0x6d, 0x8c, 0xa8, 0xb6, 0xfe];
+        bytes.extend(mac.iter().cloned());
+        bytes.extend(vec![255u8, 0, 0, 0].into_iter());
+
+        assert_eq!(compute_hmac_md5(&bytes, b"key").unwrap(), mac);
+        assert!(verify_hmac_md5(&bytes, b"key"));
+        let mut unsigned = bytes.clone();
+        for b in &mut unsigned[262..278] {
+            *b = 0;
+        }
+        sign(&mut unsigned, b"key").unwrap();
+        assert_eq!(unsigned, bytes);
+    }
+
+    #[test]
+    fn test_reconfigure_key() {
+        let mut info = vec![2u8];
+        info.extend(vec![0u8; 16].into_iter());
+        let mut bytes = signed_message(Authentication {
+            protocol: PROTOCOL_RECONFIGURE_KEY,
+            algorithm: ALGORITHM_HMAC_MD5,
+            rdm: RDM_MONOTONIC,
+            replay_detection: 1,
+            info: info,
+        });
+        sign(&mut bytes, b"reconfigure").unwrap();
+        assert!(verify_hmac_md5(&bytes, b"reconfigure"));
+        // unlike delayed authentication, this covers giaddr
+        bytes[24] = 10;
+        assert!(!verify_hmac_md5(&bytes, b"reconfigure"));
+
+        assert_eq!(compute_hmac_md5(&message(), b"reconfigure"), Err(Error::MissingOption { code: 90 }));
+        // a DHCPDISCOVER's delayed authentication option has no MAC yet
+        let bytes = signed_message(Authentication {
+            protocol: PROTOCOL_DELAYED,
+            algorithm: ALGORITHM_HMAC_MD5,
+            rdm: RDM_MONOTONIC,
+            replay_detection: 1,
+            info: vec![],
+        });
+        assert_eq!(compute_hmac_md5(&bytes, b"key"), Err(Error::InvalidOptionValue { offset: 243, code: 90 }));
+        assert!(!verify_hmac_md5(&bytes, b"key"));
+    }
+}
@@ -0,0 +1,119 @@
+//! Classless Static Route option (121, RFC 3442), and Microsoft's
+//! identical option 249
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use {Result, Error};
+use util::{ip};
+
+/// One route: `destination/prefix_len` via `gateway`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Route {
+    pub destination: IpAddr,
+    pub prefix_len: u8,
+    pub gateway: IpAddr,
+}
+
+/// Decodes a list of route descriptors. Each is a prefix length, the
+/// significant octets of the destination, then the gateway. Returns
+/// `None` for a prefix length over 32, a destination with bits set past
+/// the prefix, or a truncated descriptor.
+pub fn parse(bytes: &[u8]) -> Option<Vec<Route>> {
+    let mut routes = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        let prefix_len = rest[0];
+        if prefix_len > 32 {
+            return None;
+        }
+        let significant = significant_octets(prefix_len);
+        if rest.len() < 1 + significant + 4 {
+            return None;
+        }
+        let mut dest = [0u8; 4];
+        for (i, b) in rest[1..1 + significant].iter().enumerate() {
+            dest[i] = *b;
+        }
+        if u32::from(Ipv4Addr::from(dest)) & !prefix_mask(prefix_len) != 0 {
+            return None;
+        }
+        let gw = &rest[1 + significant..1 + significant + 4];
+        routes.push(Route {
+            destination: IpAddr::V4(Ipv4Addr::new(dest[0], dest[1], dest[2], dest[3])),
+            prefix_len: prefix_len,
+            gateway: IpAddr::V4(Ipv4Addr::new(gw[0], gw[1], gw[2], gw[3])),
+        });
+        rest = &rest[1 + significant + 4..];
+    }
+    Some(routes)
+}
+
+/// Encodes routes in the minimal form, with only the octets of each
+/// destination that the prefix length covers. A prefix length over 32,
+/// or a destination with bits set past the prefix, is an error.
+pub fn encode(tag: u8, routes: &[Route]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for route in routes {
+        if route.prefix_len > 32 {
+            return Err(Error::EncodeError(format!("Route in option {} has prefix length {}",
+                                                 tag, route.prefix_len)));
+        }
+        let dest = try!(ip(&route.destination));
+        let dest_bits = dest.iter().fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        if dest_bits & !prefix_mask(route.prefix_len) != 0 {
+            return Err(Error::EncodeError(format!("Route to {}/{} in option {} has bits set past its prefix",
+                                                 route.destination, route.prefix_len, tag)));
+        }
+        bytes.push(route.prefix_len);
+        bytes.extend(dest[..significant_octets(route.prefix_len)].iter().cloned());
+        bytes.extend(try!(ip(&route.gateway)).into_iter());
+    }
+    Ok(bytes)
+}
+
+fn prefix_mask(prefix_len: u8) -> u32 {
+    if prefix_len == 0 { 0 } else { !0u32 << (32 - prefix_len as u32) }
+}
+
+fn significant_octets(prefix_len: u8) -> usize {
+    (prefix_len as usize + 7) / 8
+}
+
+#[cfg(test)] mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use super::{parse, encode, Route};
+
+    fn v4(a: u8, b: u8, c: u8, d: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = vec![0u8, 192, 168, 1, 1,
+                         8, 10, 192, 168, 1, 2,
+                         20, 172, 16, 16, 192, 168, 1, 3,
+                         32, 10, 1, 2, 3, 192, 168, 1, 4];
+        let routes = parse(&bytes).unwrap();
+        assert_eq!(routes, vec![
+            Route { destination: v4(0, 0, 0, 0), prefix_len: 0, gateway: v4(192, 168, 1, 1) },
+            Route { destination: v4(10, 0, 0, 0), prefix_len: 8, gateway: v4(192, 168, 1, 2) },
+            Route { destination: v4(172, 16, 16, 0), prefix_len: 20, gateway: v4(192, 168, 1, 3) },
+            Route { destination: v4(10, 1, 2, 3), prefix_len: 32, gateway: v4(192, 168, 1, 4) },
+        ]);
+        assert_eq!(encode(121, &routes).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_descriptors() {
+        assert_eq!(parse(&[33u8, 10, 0, 0, 0, 0, 192, 168, 1, 1]), None);
+        assert_eq!(parse(&[24u8, 10, 0, 0, 192, 168, 1]), None);
+        assert_eq!(parse(&[16u8]), None);
+        // 172.16.31.0/20 has a bit set past the prefix, so it couldn't
+        // be encoded again
+        assert_eq!(parse(&[20u8, 172, 16, 31, 192, 168, 1, 3]), None);
+        assert!(encode(121, &[Route { destination: v4(10, 0, 0, 1), prefix_len: 8,
+                                      gateway: v4(10, 0, 0, 254) }]).is_err());
+        assert!(encode(121, &[Route { destination: v4(10, 0, 0, 0), prefix_len: 40,
+                                      gateway: v4(10, 0, 0, 254) }]).is_err());
+    }
+}
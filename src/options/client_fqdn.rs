@@ -0,0 +1,164 @@
+//! Client Fully Qualified Domain Name (option 81, RFC 4702)
+
+use std::str;
+
+use {Result, Error};
+use options::dns_name;
+
+const FLAG_S: u8 = 0x01;
+const FLAG_O: u8 = 0x02;
+const FLAG_E: u8 = 0x04;
+const FLAG_N: u8 = 0x08;
+
+/// The flags field of the Client FQDN option
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct FqdnFlags {
+    /// S: the server should do the A RR update; in a reply, whether it will
+    pub server_update: bool,
+    /// O: the server overrode the client's choice of S
+    pub server_override: bool,
+    /// E: the name is in canonical wire format rather than ASCII
+    pub encoded: bool,
+    /// N: the server should not do any DNS updates
+    pub no_server_update: bool,
+}
+
+impl FqdnFlags {
+    /// The bits RFC 4702 leaves as must-be-zero are ignored
+    pub fn from_byte(b: u8) -> FqdnFlags {
+        FqdnFlags {
+            server_update: b & FLAG_S != 0,
+            server_override: b & FLAG_O != 0,
+            encoded: b & FLAG_E != 0,
+            no_server_update: b & FLAG_N != 0,
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        let mut b = 0u8;
+        if self.server_update { b |= FLAG_S; }
+        if self.server_override { b |= FLAG_O; }
+        if self.encoded { b |= FLAG_E; }
+        if self.no_server_update { b |= FLAG_N; }
+        b
+    }
+}
+
+/// The contents of the Client FQDN option
+///
+/// `name` never has a trailing dot. In wire format, a partial name is
+/// one sent without its terminating zero-length label; in ASCII, it is
+/// one sent without a trailing dot.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientFqdn {
+    pub flags: FqdnFlags,
+    /// Deprecated: clients should send 0, and servers 255
+    pub rcode1: u8,
+    /// Deprecated, like `rcode1`
+    pub rcode2: u8,
+    pub name: String,
+    /// Whether `name` is only the first part of a name, which the
+    /// server is expected to complete
+    pub partial: bool,
+}
+
+/// Decodes an option 81 payload. The name has to be valid UTF-8 even
+/// when it isn't in wire format, and a wire format name can't use
+/// compression.
+///
+/// Setting both S and N is an error on the sender's part, but the
+/// flags are returned as they were sent so the server can decide what
+/// to make of them.
+pub fn parse(bytes: &[u8]) -> Option<ClientFqdn> {
+    if bytes.len() < 3 {
+        return None;
+    }
+    let flags = FqdnFlags::from_byte(bytes[0]);
+    let (name, partial) = if flags.encoded {
+        let (name, fully_qualified) = try_opt!(dns_name::parse_uncompressed_name(&bytes[3..]));
+        (name, !fully_qualified)
+    } else {
+        let name = try_opt!(str::from_utf8(&bytes[3..]).ok());
+        if name.ends_with('.') {
+            (name[..name.len() - 1].to_owned(), false)
+        } else {
+            (name.to_owned(), true)
+        }
+    };
+    Some(ClientFqdn {
+        flags: flags,
+        rcode1: bytes[1],
+        rcode2: bytes[2],
+        name: name,
+        partial: partial,
+    })
+}
+
+/// Encodes an option 81 payload, writing the name in whichever format
+/// the E flag asks for
+pub fn encode(fqdn: &ClientFqdn) -> Result<Vec<u8>> {
+    if fqdn.flags.server_update && fqdn.flags.no_server_update {
+        return Err(Error::EncodeError("Client FQDN can't have both the S and N flags set".to_owned()));
+    }
+    let mut bytes = vec![fqdn.flags.to_byte(), fqdn.rcode1, fqdn.rcode2];
+    if fqdn.flags.encoded {
+        try!(dns_name::encode_name(&fqdn.name, &mut bytes, None));
+        if fqdn.partial {
+            bytes.pop();
+        }
+    } else {
+        bytes.extend(fqdn.name.as_bytes().iter().cloned());
+        if !fqdn.partial {
+            bytes.push(b'.');
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)] mod tests {
+    use super::{parse, encode, ClientFqdn, FqdnFlags};
+
+    #[test]
+    fn test_wire_format() {
+        let bytes = vec![0x05u8, 0, 0, 4, b'h', b'o', b's', b't', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0];
+        let fqdn = parse(&bytes).unwrap();
+        assert_eq!(fqdn, ClientFqdn {
+            flags: FqdnFlags { server_update: true, encoded: true, ..FqdnFlags::default() },
+            rcode1: 0,
+            rcode2: 0,
+            name: "host.example".to_owned(),
+            partial: false,
+        });
+        assert_eq!(encode(&fqdn).unwrap(), bytes);
+
+        let partial = vec![0x04u8, 0, 0, 4, b'h', b'o', b's', b't'];
+        let fqdn = parse(&partial).unwrap();
+        assert_eq!((&fqdn.name[..], fqdn.partial), ("host", true));
+        assert_eq!(encode(&fqdn).unwrap(), partial);
+    }
+
+    #[test]
+    fn test_ascii() {
+        let bytes = b"\x03\xff\xffhost.example.".to_vec();
+        let fqdn = parse(&bytes).unwrap();
+        assert!(fqdn.flags.server_override && !fqdn.flags.encoded);
+        assert_eq!((fqdn.rcode1, fqdn.rcode2), (255, 255));
+        assert_eq!((&fqdn.name[..], fqdn.partial), ("host.example", false));
+        assert_eq!(encode(&fqdn).unwrap(), bytes);
+
+        let fqdn = parse(b"\x00\x00\x00host").unwrap();
+        assert_eq!((&fqdn.name[..], fqdn.partial), ("host", true));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(parse(&[0u8, 0]), None);
+        assert_eq!(parse(&[0x04u8, 0, 0, 4, b'h', 0xc0, 0]), None);
+        assert_eq!(parse(&[0u8, 0, 0, 0xff]), None);
+
+        let mut fqdn = parse(&[0x04u8, 0, 0, 0]).unwrap();
+        fqdn.flags.server_update = true;
+        fqdn.flags.no_server_update = true;
+        assert!(encode(&fqdn).is_err());
+    }
+}
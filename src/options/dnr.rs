@@ -0,0 +1,443 @@
+//! Encrypted DNS resolvers (DNR, option 162, RFC 9463), and the SVCB
+//! service parameters (RFC 9460) that describe how to reach them
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str;
+
+use {Result, Error};
+use options::dns_name;
+use util::{u16_bytes, u16_from_bytes, ip};
+
+/// One encrypted DNS resolver
+///
+/// An instance with no addresses and no service parameters is sent in
+/// "ADN-only mode", where the client has to resolve `adn` itself.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnrInstance {
+    /// Lower values are preferred
+    pub priority: u16,
+    /// Authentication domain name: the name the resolver's certificate
+    /// has to match
+    pub adn: String,
+    pub addresses: Vec<IpAddr>,
+    /// In order of increasing key, as RFC 9460 requires
+    pub svc_params: Vec<SvcParam>,
+}
+
+/// An SVCB service parameter
+#[derive(Debug, PartialEq, Clone)]
+pub enum SvcParam {
+    /// 0: keys the client has to understand to use the resolver
+    Mandatory(Vec<u16>),
+    /// 1: protocols the resolver speaks, such as "dot", "doq" and "h2"
+    Alpn(Vec<String>),
+    /// 2: the default protocol for the scheme isn't supported
+    NoDefaultAlpn,
+    /// 3
+    Port(u16),
+    /// 4
+    Ipv4Hint(Vec<IpAddr>),
+    /// 5: encrypted ClientHello configuration
+    Ech(Vec<u8>),
+    /// 6
+    Ipv6Hint(Vec<IpAddr>),
+    /// 7: URI template for DNS over HTTPS (RFC 9461)
+    DohPath(String),
+    /// 8: the resolver supports Oblivious HTTP (RFC 9540)
+    Ohttp,
+    /// Any key we don't know how to decode
+    Unknown { key: u16, value: Vec<u8> },
+}
+
+impl SvcParam {
+    pub fn key(&self) -> u16 {
+        use self::SvcParam::*;
+        match *self {
+            Mandatory(_) => 0,
+            Alpn(_) => 1,
+            NoDefaultAlpn => 2,
+            Port(_) => 3,
+            Ipv4Hint(_) => 4,
+            Ech(_) => 5,
+            Ipv6Hint(_) => 6,
+            DohPath(_) => 7,
+            Ohttp => 8,
+            Unknown { key, .. } => key,
+        }
+    }
+}
+
+/// Splits an option 162 payload into its instances, checking each of
+/// them against RFC 9463. Returns `None` if any instance is invalid.
+///
+/// Multicast and loopback addresses are left out of `addresses`, since
+/// clients have to ignore them, and an instance left with no addresses
+/// is invalid.
+pub fn parse(bytes: &[u8]) -> Option<Vec<DnrInstance>> {
+    let mut instances = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        if rest.len() < 2 {
+            return None;
+        }
+        let end = 2 + u16_from_bytes(rest) as usize;
+        if rest.len() < end {
+            return None;
+        }
+        instances.push(try_opt!(parse_instance(&rest[2..end])));
+        rest = &rest[end..];
+    }
+    Some(instances)
+}
+
+fn parse_instance(bytes: &[u8]) -> Option<DnrInstance> {
+    if bytes.len() < 3 {
+        return None;
+    }
+    let priority = u16_from_bytes(bytes);
+    let adn_end = 3 + bytes[2] as usize;
+    if bytes.len() < adn_end {
+        return None;
+    }
+    let (adn, fully_qualified) = try_opt!(dns_name::parse_uncompressed_name(&bytes[3..adn_end]));
+    if !fully_qualified || adn.is_empty() {
+        return None;
+    }
+    let rest = &bytes[adn_end..];
+    if rest.is_empty() {
+        return Some(DnrInstance { priority: priority, adn: adn, addresses: vec![], svc_params: vec![] });
+    }
+
+    let addrs_end = 1 + rest[0] as usize;
+    if rest[0] == 0 || rest[0] % 4 != 0 || rest.len() < addrs_end {
+        return None;
+    }
+    let addresses: Vec<IpAddr> = rest[1..addrs_end].chunks(4)
+                                                   .map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3]))
+                                                   .filter(|a| !a.is_multicast() && !a.is_loopback())
+                                                   .map(IpAddr::V4)
+                                                   .collect();
+    if addresses.is_empty() {
+        return None;
+    }
+    let svc_params = try_opt!(parse_svc_params(&rest[addrs_end..]));
+    if !svc_params.iter().any(|p| p.key() == 1) {
+        // alpn is required outside of ADN-only mode
+        return None;
+    }
+    if svc_params.iter().any(|p| p.key() == 4 || p.key() == 6) {
+        // the addresses go in their own field instead
+        return None;
+    }
+    Some(DnrInstance { priority: priority, adn: adn, addresses: addresses, svc_params: svc_params })
+}
+
+/// `[key(2), length(2), value...]` entries, whose keys have to be in
+/// strictly increasing order
+pub fn parse_svc_params(bytes: &[u8]) -> Option<Vec<SvcParam>> {
+    let mut params: Vec<SvcParam> = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        if rest.len() < 4 {
+            return None;
+        }
+        let key = u16_from_bytes(rest);
+        let end = 4 + u16_from_bytes(&rest[2..]) as usize;
+        if rest.len() < end {
+            return None;
+        }
+        if params.last().map_or(false, |p| p.key() >= key) {
+            return None;
+        }
+        params.push(try_opt!(parse_svc_param(key, &rest[4..end])));
+        rest = &rest[end..];
+    }
+    // every key listed as mandatory has to be there
+    if let Some(&SvcParam::Mandatory(ref keys)) = params.first() {
+        if !keys.iter().all(|k| params.iter().any(|p| p.key() == *k)) {
+            return None;
+        }
+    }
+    Some(params)
+}
+
+fn parse_svc_param(key: u16, value: &[u8]) -> Option<SvcParam> {
+    use self::SvcParam::*;
+    Some(match key {
+        0 => {
+            if value.is_empty() || value.len() % 2 != 0 {
+                return None;
+            }
+            let keys: Vec<u16> = value.chunks(2).map(u16_from_bytes).collect();
+            // no mandatory itself, and in strictly increasing order
+            if keys[0] == 0 || keys.windows(2).any(|w| w[0] >= w[1]) {
+                return None;
+            }
+            Mandatory(keys)
+        },
+        1 => {
+            let mut ids = vec![];
+            let mut rest = value;
+            while rest.len() > 0 {
+                let end = 1 + rest[0] as usize;
+                if rest[0] == 0 || rest.len() < end {
+                    return None;
+                }
+                ids.push(try_opt!(str::from_utf8(&rest[1..end]).ok()).to_owned());
+                rest = &rest[end..];
+            }
+            if ids.is_empty() {
+                return None;
+            }
+            Alpn(ids)
+        },
+        2 if value.is_empty() => NoDefaultAlpn,
+        3 if value.len() == 2 => Port(u16_from_bytes(value)),
+        4 if !value.is_empty() && value.len() % 4 == 0 => {
+            Ipv4Hint(value.chunks(4).map(|c| IpAddr::V4(Ipv4Addr::new(c[0], c[1], c[2], c[3]))).collect())
+        },
+        5 => Ech(value.to_vec()),
+        6 if !value.is_empty() && value.len() % 16 == 0 => {
+            Ipv6Hint(value.chunks(16).map(|c| {
+                let s: Vec<u16> = c.chunks(2).map(u16_from_bytes).collect();
+                IpAddr::V6(Ipv6Addr::new(s[0], s[1], s[2], s[3], s[4], s[5], s[6], s[7]))
+            }).collect())
+        },
+        7 => {
+            let path = try_opt!(str::from_utf8(value).ok());
+            // a relative URI template, with a variable for the query
+            if !path.starts_with('/') || !has_dns_variable(path) {
+                return None;
+            }
+            DohPath(path.to_owned())
+        },
+        8 if value.is_empty() => Ohttp,
+        2...4 | 6 | 8 => return None,
+        _ => Unknown { key: key, value: value.to_vec() },
+    })
+}
+
+/// Whether any expression in the URI template `path` (RFC 6570) has a
+/// `dns` variable, as in `/q{?dns}` or `/q{?dns,x}`
+fn has_dns_variable(path: &str) -> bool {
+    path.split('{').skip(1).filter_map(|e| e.find('}').map(|end| &e[..end])).any(|expr| {
+        let vars = if expr.starts_with(|c| "+#./;?&".contains(c)) { &expr[1..] } else { expr };
+        // each variable can have a prefix (`:n`) or explode (`*`) modifier
+        vars.split(',').any(|v| v.split(':').next().unwrap().trim_right_matches('*') == "dns")
+    })
+}
+
+/// Inverse of `parse`
+pub fn encode(instances: &[DnrInstance]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for instance in instances {
+        if instance.adn.is_empty() || instance.adn == "." {
+            return Err(Error::EncodeError("DNR authentication domain name can't be the root".to_owned()));
+        }
+        let mut data = u16_bytes(instance.priority);
+        let mut adn = vec![];
+        try!(dns_name::encode_name(&instance.adn, &mut adn, None));
+        data.push(adn.len() as u8);
+        data.extend(adn.into_iter());
+        if !instance.addresses.is_empty() || !instance.svc_params.is_empty() {
+            if instance.addresses.is_empty() || instance.addresses.len() > 63 {
+                return Err(Error::EncodeError(format!("DNR instance for {} has {} addresses, but needs 1 to 63",
+                                                     instance.adn, instance.addresses.len())));
+            }
+            if !instance.svc_params.iter().any(|p| p.key() == 1) {
+                return Err(Error::EncodeError(format!("DNR instance for {} has no alpn", instance.adn)));
+            }
+            if instance.svc_params.iter().any(|p| p.key() == 4 || p.key() == 6) {
+                return Err(Error::EncodeError(format!("DNR instance for {} has address hints", instance.adn)));
+            }
+            data.push(4 * instance.addresses.len() as u8);
+            for a in &instance.addresses {
+                data.extend(try!(ip(a)).into_iter());
+            }
+            data.extend(try!(encode_svc_params(&instance.svc_params)).into_iter());
+        }
+        if data.len() > 0xffff {
+            return Err(Error::EncodeError(format!("DNR instance for {} is {} bytes long", instance.adn, data.len())));
+        }
+        bytes.extend(u16_bytes(data.len() as u16).into_iter());
+        bytes.extend(data.into_iter());
+    }
+    Ok(bytes)
+}
+
+/// Inverse of `parse_svc_params`. Fails if the keys aren't in strictly
+/// increasing order.
+pub fn encode_svc_params(params: &[SvcParam]) -> Result<Vec<u8>> {
+    use self::SvcParam::*;
+    let mut bytes = vec![];
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 && params[i - 1].key() >= param.key() {
+            return Err(Error::EncodeError(format!("SvcParam key {} is out of order", param.key())));
+        }
+        let value = match *param {
+            Mandatory(ref keys) => keys.iter().flat_map(|k| u16_bytes(*k).into_iter()).collect(),
+            Alpn(ref ids) => {
+                let mut value = vec![];
+                for id in ids {
+                    if id.is_empty() || id.len() > 255 {
+                        return Err(Error::EncodeError(format!("alpn id {:?} is the wrong length", id)));
+                    }
+                    value.push(id.len() as u8);
+                    value.extend(id.as_bytes().iter().cloned());
+                }
+                value
+            },
+            NoDefaultAlpn | Ohttp => vec![],
+            Port(p) => u16_bytes(p),
+            Ipv4Hint(ref addrs) => {
+                let mut value = vec![];
+                for a in addrs {
+                    value.extend(try!(ip(a)).into_iter());
+                }
+                value
+            },
+            Ech(ref config) => config.clone(),
+            Ipv6Hint(ref addrs) => {
+                let mut value = vec![];
+                for a in addrs {
+                    match *a {
+                        IpAddr::V6(a) => for s in a.segments().iter() {
+                            value.extend(u16_bytes(*s).into_iter());
+                        },
+                        IpAddr::V4(_) => return Err(Error::EncodeError("ipv6hint can only hold IPv6 addresses".to_owned())),
+                    }
+                }
+                value
+            },
+            DohPath(ref path) => path.as_bytes().to_vec(),
+            Unknown { ref value, .. } => value.clone(),
+        };
+        if value.len() > 0xffff {
+            return Err(Error::EncodeError(format!("SvcParam {} is {} bytes long", param.key(), value.len())));
+        }
+        bytes.extend(u16_bytes(param.key()).into_iter());
+        bytes.extend(u16_bytes(value.len() as u16).into_iter());
+        bytes.extend(value.into_iter());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)] mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use super::{parse, encode, parse_svc_params, encode_svc_params, DnrInstance};
+    use super::SvcParam::*;
+
+    fn instance() -> Vec<u8> {
+        vec![0u8, 51,                                   // instance length
+             0, 1,                                      // priority
+             13, 3, b'd', b'n', b's', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0,
+             4, 192, 0, 2, 1,                           // addresses
+             0, 1, 0, 7, 2, b'h', b'2', 3, b'd', b'o', b't',
+             0, 3, 0, 2, 1, 187,
+             0, 7, 0, 9, b'/', b'q', b'{', b'?', b'd', b'n', b's', b'}', b'&']
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut bytes = instance();
+        // and one in ADN-only mode
+        bytes.extend(vec![0u8, 6, 0, 2, 3, 1, b'r', 0].into_iter());
+        let instances = parse(&bytes).unwrap();
+        assert_eq!(instances, vec![
+            DnrInstance {
+                priority: 1,
+                adn: "dns.example".to_owned(),
+                addresses: vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))],
+                svc_params: vec![Alpn(vec!["h2".to_owned(), "dot".to_owned()]),
+                                 Port(443),
+                                 DohPath("/q{?dns}&".to_owned())],
+            },
+            DnrInstance { priority: 2, adn: "r".to_owned(), addresses: vec![], svc_params: vec![] },
+        ]);
+        assert_eq!(encode(&instances).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_instances() {
+        // instance length runs off the end
+        let mut bytes = instance();
+        bytes[1] = 52;
+        assert_eq!(parse(&bytes), None);
+
+        // address length isn't a multiple of 4
+        let mut bytes = instance();
+        bytes[18] = 3;
+        assert_eq!(parse(&bytes), None);
+
+        // ADN is a partial name
+        assert_eq!(parse(&[0u8, 5, 0, 2, 2, 1, b'r']), None);
+        // or the root
+        assert_eq!(parse(&[0u8, 4, 0, 2, 1, 0]), None);
+
+        // no alpn
+        let no_alpn = vec![0u8, 17, 0, 2, 3, 1, b'r', 0, 4, 10, 0, 0, 1, 0, 3, 0, 2, 0, 53];
+        assert_eq!(parse(&no_alpn), None);
+    }
+
+    #[test]
+    fn test_loopback_addresses_are_dropped() {
+        let mut bytes = instance();
+        bytes[1] = 55;
+        bytes[18] = 8;
+        for (i, b) in [127u8, 0, 0, 1].iter().enumerate() {
+            bytes.insert(19 + i, *b);
+        }
+        assert_eq!(parse(&bytes).unwrap()[0].addresses, vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]);
+
+        // an instance with nothing else left is invalid
+        let mut bytes = instance();
+        bytes[19] = 127;
+        assert_eq!(parse(&bytes), None);
+        bytes[19] = 224;
+        assert_eq!(parse(&bytes), None);
+    }
+
+    #[test]
+    fn test_address_hints() {
+        let mut bytes = instance();
+        bytes[1] += 8;
+        for (i, b) in [0u8, 4, 0, 4, 192, 0, 2, 2].iter().enumerate() {
+            bytes.insert(40 + i, *b);
+        }
+        assert!(parse_svc_params(&bytes[23..]).is_some());
+        assert_eq!(parse(&bytes), None);
+
+        let mut instances = parse(&instance()).unwrap();
+        instances[0].svc_params.insert(2, Ipv4Hint(vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2))]));
+        assert!(encode(&instances).is_err());
+    }
+
+    #[test]
+    fn test_svc_params() {
+        let bytes = vec![0u8, 0, 0, 2, 0, 3,
+                         0, 2, 0, 0,
+                         0, 3, 0, 2, 3, 85,
+                         0, 6, 0, 16, 0x20, 1, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+                         0, 9, 0, 1, 42];
+        let params = parse_svc_params(&bytes).unwrap();
+        assert_eq!(params, vec![
+            Mandatory(vec![3]),
+            NoDefaultAlpn,
+            Port(853),
+            Ipv6Hint(vec![IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))]),
+            Unknown { key: 9, value: vec![42] },
+        ]);
+        assert_eq!(encode_svc_params(&params).unwrap(), bytes);
+
+        // out of order
+        assert_eq!(parse_svc_params(&[0u8, 3, 0, 2, 3, 85, 0, 2, 0, 0]), None);
+        // mandatory key that isn't there
+        assert_eq!(parse_svc_params(&[0u8, 0, 0, 2, 0, 3]), None);
+        // dohpath without the dns variable
+        assert_eq!(parse_svc_params(&[0u8, 7, 0, 2, b'/', b'q']), None);
+        assert_eq!(parse_svc_params(b"\x00\x07\x00\x09/q{?dnsx}"), None);
+        assert_eq!(parse_svc_params(b"\x00\x07\x00\x0a/{dns}{?x}"), Some(vec![DohPath("/{dns}{?x}".to_owned())]));
+        assert_eq!(parse_svc_params(b"\x00\x07\x00\x0a/q{?dns,x}"), Some(vec![DohPath("/q{?dns,x}".to_owned())]));
+        assert!(encode_svc_params(&[Port(853), NoDefaultAlpn]).is_err());
+    }
+}
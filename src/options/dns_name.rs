@@ -0,0 +1,231 @@
+//! DNS wire-format domain names (RFC 1035 section 3.1), with the
+//! message compression of section 4.1.4, for options like the Domain
+//! Search List (119, RFC 3397)
+//!
+//! Names are written without a trailing dot, and the root domain is
+//! the empty string.
+
+use std::collections::{HashMap};
+use std::str;
+
+use {Result, Error};
+
+/// Longest a name can be on the wire, length bytes included
+const MAX_NAME_LEN: usize = 255;
+/// Longest a single label can be
+const MAX_LABEL_LEN: usize = 63;
+/// Compression pointers only have 14 bits for the offset
+const MAX_POINTER: usize = 0x3fff;
+
+/// Decodes the name that starts at `start` in `bytes`, following any
+/// compression pointers. Returns the name and the offset just past it.
+///
+/// Pointers have to point backwards, to before wherever the previous
+/// pointer (or the name itself) started, so a loop of pointers is
+/// rejected rather than followed forever. Labels have to be valid
+/// UTF-8 and can't contain dots.
+pub fn parse_name(bytes: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<&str> = vec![];
+    let mut wire_len = 1;
+    let mut pos = start;
+    let mut limit = start;
+    let mut end = None;
+    loop {
+        if pos >= bytes.len() {
+            return None;
+        }
+        let b = bytes[pos];
+        match b & 0xc0 {
+            0x00 if b == 0 => {
+                if end.is_none() {
+                    end = Some(pos + 1);
+                }
+                break;
+            },
+            0x00 => {
+                let len = b as usize;
+                if pos + 1 + len > bytes.len() {
+                    return None;
+                }
+                let label = try_opt!(str::from_utf8(&bytes[pos + 1..pos + 1 + len]).ok());
+                if label.contains('.') {
+                    return None;
+                }
+                wire_len += 1 + len;
+                if wire_len > MAX_NAME_LEN {
+                    return None;
+                }
+                labels.push(label);
+                pos += 1 + len;
+            },
+            0xc0 => {
+                if pos + 1 >= bytes.len() {
+                    return None;
+                }
+                let target = ((b & 0x3f) as usize) << 8 | bytes[pos + 1] as usize;
+                if end.is_none() {
+                    end = Some(pos + 2);
+                }
+                if target >= limit {
+                    return None;
+                }
+                limit = target;
+                pos = target;
+            },
+            // 0x40 and 0x80 are reserved label types
+            _ => return None,
+        }
+    }
+    Some((labels.join("."), end.unwrap()))
+}
+
+/// Decodes a list of names that fill `bytes` exactly
+pub fn parse_names(bytes: &[u8]) -> Option<Vec<String>> {
+    let mut names = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let (name, next) = try_opt!(parse_name(bytes, pos));
+        names.push(name);
+        pos = next;
+    }
+    Some(names)
+}
+
+/// Decodes `bytes` as exactly one name
+pub fn parse_single_name(bytes: &[u8]) -> Option<String> {
+    let (name, next) = try_opt!(parse_name(bytes, 0));
+    if next != bytes.len() {
+        return None;
+    }
+    Some(name)
+}
+
+/// Decodes an uncompressed name that fills `bytes`, where the final
+/// zero-length label may be left off to mark a partial name (as in RFC
+/// 4702). Returns the name and whether it was fully qualified.
+pub fn parse_uncompressed_name(bytes: &[u8]) -> Option<(String, bool)> {
+    let mut labels = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let len = bytes[pos] as usize;
+        if len == 0 {
+            if pos + 1 != bytes.len() {
+                return None;
+            }
+            return Some((labels.join("."), true));
+        }
+        // also rules out pointers and the reserved label types
+        if len > MAX_LABEL_LEN || pos + 1 + len > bytes.len() {
+            return None;
+        }
+        let label = try_opt!(str::from_utf8(&bytes[pos + 1..pos + 1 + len]).ok());
+        if label.contains('.') {
+            return None;
+        }
+        labels.push(label);
+        pos += 1 + len;
+        // counting the terminating label, even if it's left off
+        if pos + 1 > MAX_NAME_LEN {
+            return None;
+        }
+    }
+    Some((labels.join("."), false))
+}
+
+/// Encodes a list of names, using compression pointers for any suffix
+/// that has already been written
+pub fn encode_names(names: &[String]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut suffixes = HashMap::new();
+    for name in names {
+        try!(encode_name(name, &mut bytes, Some(&mut suffixes)));
+    }
+    Ok(bytes)
+}
+
+/// Appends `name` to `bytes` in wire format. When `suffixes` is given,
+/// it maps suffixes already in `bytes` to their offsets; they are used
+/// to compress `name`, and its own suffixes are added to the map.
+pub fn encode_name(name: &str, bytes: &mut Vec<u8>, mut suffixes: Option<&mut HashMap<String, usize>>) -> Result<()> {
+    let name = if name.ends_with('.') { &name[..name.len() - 1] } else { name };
+    let labels: Vec<&str> = if name.is_empty() { vec![] } else { name.split('.').collect() };
+    let wire_len = labels.iter().fold(1, |acc, l| acc + 1 + l.len());
+    if wire_len > MAX_NAME_LEN {
+        return Err(Error::EncodeError(format!("Domain name {} is too long", name)));
+    }
+    for (i, label) in labels.iter().enumerate() {
+        if label.is_empty() || label.len() > MAX_LABEL_LEN {
+            return Err(Error::EncodeError(format!("Domain name {} has a label that is empty or too long", name)));
+        }
+        if let Some(ref mut map) = suffixes {
+            let suffix = labels[i..].join(".");
+            if let Some(&offset) = map.get(&suffix) {
+                bytes.push(0xc0 | (offset >> 8) as u8);
+                bytes.push(offset as u8);
+                return Ok(());
+            }
+            if bytes.len() <= MAX_POINTER {
+                map.insert(suffix, bytes.len());
+            }
+        }
+        bytes.push(label.len() as u8);
+        bytes.extend(label.as_bytes().iter().cloned());
+    }
+    bytes.push(0u8);
+    Ok(())
+}
+
+#[cfg(test)] mod tests {
+    use super::{parse_name, parse_names, parse_single_name, parse_uncompressed_name, encode_names};
+
+    #[test]
+    fn test_rfc3397_example() {
+        // the example from RFC 3397 section 3, "eng.apple.com." and
+        // "marketing.apple.com."
+        let bytes = vec![3u8, b'e', b'n', b'g', 5, b'a', b'p', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0,
+                         9, b'm', b'a', b'r', b'k', b'e', b't', b'i', b'n', b'g', 0xc0, 4];
+        let names = vec!["eng.apple.com".to_owned(), "marketing.apple.com".to_owned()];
+        assert_eq!(parse_names(&bytes).unwrap(), names);
+        assert_eq!(encode_names(&names).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_root_and_trailing_dot() {
+        assert_eq!(parse_single_name(&[0u8]).unwrap(), "");
+        assert_eq!(encode_names(&["".to_owned(), "a.".to_owned()]).unwrap(),
+                   vec![0u8, 1, b'a', 0]);
+        assert_eq!(parse_single_name(&[0u8, 0]), None);
+    }
+
+    #[test]
+    fn test_pointer_loops() {
+        // points at itself
+        assert_eq!(parse_name(&[0xc0u8, 0], 0), None);
+        // two pointers that point at each other
+        assert_eq!(parse_name(&[1u8, b'a', 0xc0, 4, 0xc0, 2], 4), None);
+        // forward pointer
+        assert_eq!(parse_name(&[0xc0u8, 2, 0], 0), None);
+        // pointer off the end
+        assert_eq!(parse_names(&[1u8, b'a', 0, 0xc0]), None);
+    }
+
+    #[test]
+    fn test_bad_names() {
+        assert_eq!(parse_names(&[3u8, b'a', b'b']), None);
+        assert_eq!(parse_names(&[0x40u8, 0]), None);
+        assert_eq!(parse_names(&[3u8, b'a', b'.', b'b', 0]), None);
+        assert!(encode_names(&["a..b".to_owned()]).is_err());
+        assert!(encode_names(&[String::from_utf8(vec![b'a'; 64]).unwrap()]).is_err());
+        let long = vec![String::from_utf8(vec![b'a'; 63]).unwrap(); 4].join(".");
+        assert!(encode_names(&[long]).is_err());
+    }
+
+    #[test]
+    fn test_uncompressed_names() {
+        assert_eq!(parse_uncompressed_name(&[1u8, b'a', 1, b'b', 0]), Some(("a.b".to_owned(), true)));
+        assert_eq!(parse_uncompressed_name(&[1u8, b'a', 1, b'b']), Some(("a.b".to_owned(), false)));
+        assert_eq!(parse_uncompressed_name(&[]), Some(("".to_owned(), false)));
+        assert_eq!(parse_uncompressed_name(&[1u8, b'a', 0xc0, 0]), None);
+        assert_eq!(parse_uncompressed_name(&[1u8, b'a', 0, 0]), None);
+    }
+}
@@ -0,0 +1,398 @@
+use options::{DhcpOption, authentication, client_fqdn, relay_agent, classless_route, dns_name, vendor_class, dnr, address_sharing, geolocation};
+use options::DhcpOption::*;
+use {Result, Error};
+use util::{u16_bytes, u32_bytes, ip};
+use std::net::{IpAddr};
+
+/// Encodes `options` into an options area, followed by an `End`.
+///
+/// Any `Pad` or `End` in `options` is written out as-is, so a caller
+/// that wants to pad the packet can do so by including `Pad`s.
+pub fn encode(options: &[DhcpOption]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for option in options {
+        try!(encode_option(option, &mut bytes));
+    }
+    bytes.push(255u8);
+    Ok(bytes)
+}
+
+/// Appends the wire form of a single option, `[tag, length, data...]`,
+/// to `bytes`. `Pad` and `End` are written as a single byte, and data
+/// longer than 255 bytes is split over several options.
+pub fn encode_option(option: &DhcpOption, bytes: &mut Vec<u8>) -> Result<()> {
+    let (tag, data) = match *option {
+        Pad => { bytes.push(0u8); return Ok(()); },
+        End => { bytes.push(255u8); return Ok(()); },
+        SubnetMask(ref a) => (1u8, try!(ip(a))),
+        TimeOffset(t) => (2u8, u32_bytes(t as u32)),
+        Router(ref a) => (3u8, try!(many_ips(a))),
+        TimeServer(ref a) => (4u8, try!(many_ips(a))),
+        NameServer(ref a) => (5u8, try!(many_ips(a))),
+        DomainNameServer(ref a) => (6u8, try!(many_ips(a))),
+        LogServer(ref a) => (7u8, try!(many_ips(a))),
+        CookieServer(ref a) => (8u8, try!(many_ips(a))),
+        LprServer(ref a) => (9u8, try!(many_ips(a))),
+        ImpressServer(ref a) => (10u8, try!(many_ips(a))),
+        ResourceLocationServer(ref a) => (11u8, try!(many_ips(a))),
+        HostName(ref s) => (12u8, s.as_bytes().to_vec()),
+        BootFileSize(s) => (13u8, u16_bytes(s)),
+        MeritDumpFile(ref s) => (14u8, s.as_bytes().to_vec()),
+        DomainName(ref s) => (15u8, s.as_bytes().to_vec()),
+        SwapServer(ref a) => (16u8, try!(ip(a))),
+        RootPath(ref s) => (17u8, s.as_bytes().to_vec()),
+        ExtensionsPath(ref s) => (18u8, s.as_bytes().to_vec()),
+        IPForwarding(b) => (19u8, bool_bytes(b)),
+
+        NonLocalSourceRouting(b) => (20u8, bool_bytes(b)),
+        PolicyFilter(ref pairs) => (21u8, try!(ip_pairs(pairs))),
+        MaxDatagramReassemblySize(s) => (22u8, u16_bytes(s)),
+        DefaultIpTtl(t) => (23u8, vec![t]),
+        PathMtuAgingTimeout(t) => (24u8, u32_bytes(t)),
+        PathMtuPlateauTable(ref sizes) => {
+            (25u8, sizes.iter().flat_map(|s| u16_bytes(*s).into_iter()).collect())
+        },
+
+        InterfaceMtu(m) => (26u8, u16_bytes(m)),
+        AllSubnetsAreLocal(b) => (27u8, bool_bytes(b)),
+        BroadcastAddress(ref a) => (28u8, try!(ip(a))),
+        PerformMaskDiscovery(b) => (29u8, bool_bytes(b)),
+        MaskSupplier(b) => (30u8, bool_bytes(b)),
+        PerformRouterDiscovery(b) => (31u8, bool_bytes(b)),
+        RouterSolicitationAddress(ref a) => (32u8, try!(ip(a))),
+        StaticRoute(ref pairs) => (33u8, try!(ip_pairs(pairs))),
+
+        TrailerEncapsulation(b) => (34u8, bool_bytes(b)),
+        ArpCacheTimeout(t) => (35u8, u32_bytes(t)),
+        EthernetEncapsulation(b) => (36u8, bool_bytes(b)),
+
+        TcpDefaultTtl(t) => (37u8, vec![t]),
+        TcpKeepaliveInterval(i) => (38u8, u32_bytes(i)),
+        TcpKeepaliveGarbage(b) => (39u8, bool_bytes(b)),
+
+        NisDomain(ref s) => (40u8, s.as_bytes().to_vec()),
+        NetworkInformationServers(ref a) => (41u8, try!(many_ips(a))),
+        NtpServers(ref a) => (42u8, try!(many_ips(a))),
+        VendorExtensions(ref v) => (43u8, v.clone()),
+        NetBiosNameServers(ref a) => (44u8, try!(many_ips(a))),
+        NetBiosDatagramDistributionServer(ref a) => (45u8, try!(many_ips(a))),
+        NetBiosNodeType(t) => (46u8, vec![t as u8]),
+        NetBiosScope(ref s) => (47u8, s.as_bytes().to_vec()),
+        XFontServer(ref a) => (48u8, try!(many_ips(a))),
+        XDisplayManager(ref a) => (49u8, try!(many_ips(a))),
+
+        RequestedIpAddress(ref a) => (50u8, try!(ip(a))),
+        IpAddressLeaseTime(t) => (51u8, u32_bytes(t)),
+        OptionOverload(o) => (52u8, vec![o as u8]),
+        MessageType(m) => (53u8, vec![m.to_byte()]),
+        ServerIdentifier(ref a) => (54u8, try!(ip(a))),
+        ParamRequestList(ref v) => (55u8, v.clone()),
+        Message(ref s) => (56u8, s.as_bytes().to_vec()),
+        MaxMessageSize(s) => (57u8, u16_bytes(s)),
+        RenewalTimeValue(t) => (58u8, u32_bytes(t)),
+        RebindingTimeValue(t) => (59u8, u32_bytes(t)),
+        ClassIdentifier(ref v) => (60u8, v.clone()),
+        ClientIdentifier { id_type, ref id } => {
+            let mut data = vec![id_type];
+            data.extend(id.iter().cloned());
+            (61u8, data)
+        },
+        NetWareIpDomain(ref s) => (62u8, s.as_bytes().to_vec()),
+        NetWareIpInformation(ref subs) => (63u8, try!(sub_options(63u8, subs))),
+        NisPlusDomain(ref s) => (64u8, s.as_bytes().to_vec()),
+        NisPlusServers(ref a) => (65u8, try!(many_ips(a))),
+        TftpServerName(ref s) => (66u8, s.as_bytes().to_vec()),
+        BootfileName(ref s) => (67u8, s.as_bytes().to_vec()),
+        MobileIpHomeAgent(ref a) => (68u8, try!(many_ips(a))),
+        SmtpServer(ref a) => (69u8, try!(many_ips(a))),
+        Pop3Server(ref a) => (70u8, try!(many_ips(a))),
+        NntpServer(ref a) => (71u8, try!(many_ips(a))),
+        WwwServer(ref a) => (72u8, try!(many_ips(a))),
+        FingerServer(ref a) => (73u8, try!(many_ips(a))),
+        IrcServer(ref a) => (74u8, try!(many_ips(a))),
+        StreetTalkServer(ref a) => (75u8, try!(many_ips(a))),
+        StdaServer(ref a) => (76u8, try!(many_ips(a))),
+
+        ClientFqdn(ref fqdn) => (81u8, try!(client_fqdn::encode(fqdn))),
+        RelayAgentInformation(ref subs) => (82u8, try!(relay_agent::encode(subs))),
+        BcmcsControllerDomainNames(ref names) => (88u8, try!(dns_name::encode_names(names))),
+        Authentication(ref auth) => (90u8, authentication::encode(auth)),
+        ClientLastTransactionTime(t) => (91u8, u32_bytes(t)),
+        AssociatedIp(ref a) => (92u8, try!(many_ips(a))),
+        ClientSystemArchitecture(ref archs) => {
+            (93u8, archs.iter().flat_map(|a| u16_bytes(a.to_u16()).into_iter()).collect())
+        },
+        ClientNetworkInterfaceIdentifier { interface_type, major, minor } => (94u8, vec![interface_type, major, minor]),
+        ClientMachineIdentifier(ref uuid) => {
+            let mut data = vec![0u8];
+            data.extend(uuid.0.iter().cloned());
+            (97u8, data)
+        },
+        CivicAddress(ref address) => (99u8, try!(geolocation::encode_civic_address(address))),
+        PosixTimeZone(ref tz) => (100u8, tz.wire_string().into_bytes()),
+        TzdbTimeZone(ref name) => (101u8, name.as_bytes().to_vec()),
+        Ipv6OnlyPreferred(t) => (108u8, u32_bytes(t)),
+        CaptivePortal(ref s) => (114u8, s.as_bytes().to_vec()),
+        DomainSearch(ref names) => (119u8, try!(dns_name::encode_names(names))),
+        ClasslessStaticRoute(ref routes) => (121u8, try!(classless_route::encode(121u8, routes))),
+        GeoConf(ref coordinates) => (123u8, try!(geolocation::encode_coordinates(coordinates))),
+        VendorIdentifyingVendorClass(ref classes) => (124u8, try!(vendor_class::encode(classes))),
+        VendorIdentifyingVendorSpecific(ref blocks) =>
+            (125u8, try!(relay_agent::encode_vendor_specific_information(125u8, blocks))),
+        LostServer(ref name) => (137u8, try!(dns_name::encode_names(&[name.clone()]))),
+        SipUaConfigServiceDomains(ref names) => (141u8, try!(dns_name::encode_names(names))),
+        GeoLoc(ref coordinates) => (144u8, try!(geolocation::encode_coordinates(coordinates))),
+        StatusCode { code, ref message } => {
+            let mut data = vec![code.to_byte()];
+            data.extend(message.as_bytes().iter().cloned());
+            (151u8, data)
+        },
+        BaseTime(t) => (152u8, u32_bytes(t)),
+        StartTimeOfState(t) => (153u8, u32_bytes(t)),
+        QueryStartTime(t) => (154u8, u32_bytes(t)),
+        QueryEndTime(t) => (155u8, u32_bytes(t)),
+        DhcpState(s) => (156u8, vec![s.to_byte()]),
+        DataSource(s) => (157u8, vec![s]),
+        V4PortParams(ref params) => (159u8, try!(address_sharing::encode_port_parameters(params))),
+        LegacyCaptivePortal(ref s) => (160u8, s.as_bytes().to_vec()),
+        MudUrl(ref s) => (161u8, s.as_bytes().to_vec()),
+        EncryptedDns(ref instances) => (162u8, try!(dnr::encode(instances))),
+        SixRdConfig(ref six_rd) => (212u8, try!(address_sharing::encode_six_rd(six_rd))),
+        AccessDomain(ref name) => (213u8, try!(dns_name::encode_names(&[name.clone()]))),
+        MsClasslessStaticRoute(ref routes) => (249u8, try!(classless_route::encode(249u8, routes))),
+        Unknown { code, ref data } => (code, data.clone()),
+    };
+    if data.is_empty() {
+        bytes.push(tag);
+        bytes.push(0u8);
+    }
+    // anything that doesn't fit in one option is split over as many as
+    // it takes (RFC 3396)
+    for chunk in data.chunks(255) {
+        bytes.push(tag);
+        bytes.push(chunk.len() as u8);
+        bytes.extend(chunk.iter().cloned());
+    }
+    Ok(())
+}
+
+fn bool_bytes(b: bool) -> Vec<u8> {
+    vec![if b { 1u8 } else { 0u8 }]
+}
+
+fn many_ips(addrs: &[IpAddr]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for addr in addrs {
+        bytes.extend(try!(ip(addr)).into_iter());
+    }
+    Ok(bytes)
+}
+
+fn ip_pairs(pairs: &[(IpAddr, IpAddr)]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for &(ref a, ref b) in pairs {
+        bytes.extend(try!(ip(a)).into_iter());
+        bytes.extend(try!(ip(b)).into_iter());
+    }
+    Ok(bytes)
+}
+
+/// The `[code, length, data...]` encoding of sub-options, for the
+/// option with code `tag`
+pub fn sub_options(tag: u8, subs: &[(u8, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for &(code, ref data) in subs {
+        if data.len() > 255 {
+            return Err(Error::EncodeError(format!("Sub-option {} of option {} is {} bytes long",
+                                                 code, tag, data.len())));
+        }
+        bytes.push(code);
+        bytes.push(data.len() as u8);
+        bytes.extend(data.iter().cloned());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)] mod tests {
+    use options::DhcpOption::*;
+    use options::{self, DhcpMessageTypes, NodeType, parse};
+    use super::{encode, encode_option};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_encode_options() {
+        let options = vec![
+            MessageType(DhcpMessageTypes::Offer),
+            SubnetMask(IpAddr::V4(Ipv4Addr::new(255, 255, 255, 0))),
+            Router(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+                        IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]),
+            TimeOffset(-3600),
+            DomainName("example.com".to_owned()),
+            NetBiosNodeType(NodeType::H),
+            PathMtuPlateauTable(vec![1500, 576]),
+            IPForwarding(true),
+        ];
+        let bytes = encode(&options).unwrap();
+        assert_eq!(&bytes[..3], &[53u8, 1, 2][..]);
+        assert_eq!(&bytes[3..9], &[1u8, 4, 255, 255, 255, 0][..]);
+        assert_eq!(&bytes[9..19], &[3u8, 8, 10, 0, 0, 1, 10, 0, 0, 2][..]);
+        assert_eq!(&bytes[19..25], &[2u8, 4, 255, 255, 241, 240][..]);
+        assert_eq!(bytes[bytes.len() - 1], 255u8);
+        assert_eq!(parse(&bytes).unwrap(), options);
+    }
+
+    #[test]
+    fn test_round_trip_bytes() {
+        let bytes = vec![53u8, 1, 1,
+                         50, 4, 192, 168, 1, 100,
+                         55, 4, 1, 3, 6, 15,
+                         12, 4, 104, 111, 115, 116,
+                         255];
+        let options = parse(&bytes).unwrap();
+        assert_eq!(encode(&options).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_round_trip_unknown() {
+        let bytes = vec![53u8, 1, 3,
+                         224, 5, 0, 0, 13, 233, 1,
+                         116, 1, 1,
+                         255];
+        let options = parse(&bytes).unwrap();
+        assert_eq!(options[1], Unknown { code: 224, data: vec![0, 0, 13, 233, 1] });
+        assert_eq!(encode(&options).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_round_trip_rfc2132_extensions() {
+        let bytes = vec![21u8, 8, 10, 0, 0, 0, 255, 0, 0, 0,
+                         57, 2, 5, 220,
+                         60, 4, 77, 83, 70, 84,
+                         61, 7, 1, 0, 17, 34, 51, 68, 85,
+                         62, 2, 110, 119,
+                         63, 5, 5, 1, 1, 6, 0,
+                         65, 4, 10, 0, 0, 65,
+                         67, 4, 98, 111, 111, 116,
+                         72, 8, 10, 0, 0, 80, 10, 0, 0, 81,
+                         255];
+        let options = parse(&bytes).unwrap();
+        assert_eq!(options.len(), 9);
+        assert_eq!(encode(&options).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_round_trip_domain_names() {
+        let bytes = vec![88u8, 5, 1, b'b', 1, b'c', 0,
+                         119, 11, 1, b'a', 1, b'b', 1, b'c', 0, 1, b'd', 0xc0, 2,
+                         141, 3, 1, b's', 0,
+                         213, 1, 0,
+                         255];
+        let options = parse(&bytes).unwrap();
+        assert_eq!(options[1], DomainSearch(vec!["a.b.c".to_owned(), "d.b.c".to_owned()]));
+        assert_eq!(encode(&options).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_encode_pad_and_end() {
+        let mut bytes = vec![];
+        encode_option(&Pad, &mut bytes).unwrap();
+        encode_option(&End, &mut bytes).unwrap();
+        assert_eq!(bytes, vec![0u8, 255]);
+    }
+
+    #[test]
+    fn test_round_trip_leasequery() {
+        let bytes = vec![91u8, 4, 0, 0, 0, 60,
+                         92, 4, 10, 0, 0, 1,
+                         151, 6, 4, b'n', b'o', b'p', b'e', b'!',
+                         152, 4, 0, 0, 1, 0,
+                         156, 1, 8,
+                         157, 1, 0,
+                         255];
+        assert_eq!(encode(&parse(&bytes).unwrap()).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_round_trip_time_zones() {
+        let mut bytes = vec![100u8, 32];
+        bytes.extend(b"<-03>3<-02>,M3.5.0/-2,M10.5.0/-1".iter().cloned());
+        bytes.extend(vec![101u8, 12].into_iter());
+        bytes.extend(b"America/Nuuk".iter().cloned());
+        bytes.push(255);
+        assert_eq!(encode(&parse(&bytes).unwrap()).unwrap(), bytes);
+
+        // the example from RFC 4833, which isn't in the shortest form
+        let mut bytes = vec![100u8, 35];
+        bytes.extend(b"EST5EDT4,M3.2.0/02:00,M11.1.0/02:00".iter().cloned());
+        bytes.push(255);
+        let mut options = parse(&bytes).unwrap();
+        assert_eq!(encode(&options).unwrap(), bytes);
+
+        // but a change to the fields gets sent
+        if let PosixTimeZone(ref mut tz) = options[0] {
+            tz.dst.as_mut().unwrap().offset = None;
+        }
+        let bytes = encode(&options).unwrap();
+        assert_eq!(&bytes[2..bytes.len() - 1], &b"EST5EDT,M3.2.0,M11.1.0"[..]);
+        assert_eq!(parse(&bytes).unwrap(), options);
+    }
+
+    #[test]
+    fn test_round_trip_address_sharing() {
+        let bytes = vec![159u8, 4, 0, 0, 0, 0,
+                         212, 26, 0, 28, 0x20, 0x01, 0x0d, 0xb0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                         192, 0, 2, 1, 192, 0, 2, 2,
+                         255];
+        assert_eq!(encode(&parse(&bytes).unwrap()).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_round_trip_geolocation() {
+        let bytes = vec![99u8, 9, 2, b'C', b'H', 3, 4, b'B', b'e', b'r', b'n',
+                         123, 16, 0x48, 0x4d, 0x00, 0x00, 0x00, 0x4b, 0x65, 0x80, 0x00, 0x00, 0x15, 0x00, 0x00, 0x0f,
+                         0xc0, 0x41,
+                         144, 16, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x43,
+                         255];
+        assert_eq!(encode(&parse(&bytes).unwrap()).unwrap(), bytes);
+
+        // an address too long for one option is split over two
+        let options = vec![CivicAddress(options::CivicAddress {
+            what: 2,
+            country_code: "CH".to_owned(),
+            elements: vec![(19, "x".repeat(200)), (34, "y".repeat(100))],
+        })];
+        let bytes = encode(&options).unwrap();
+        assert_eq!(&bytes[..2], &[99u8, 255][..]);
+        assert_eq!(&bytes[257..259], &[99u8, 52][..]);
+        assert_eq!(parse(&bytes).unwrap(), options);
+    }
+
+    #[test]
+    fn test_round_trip_encrypted_dns() {
+        let bytes = vec![162u8, 27,
+                         0, 25, 0, 1, 3, 1, b'r', 0, 4, 10, 0, 0, 53,
+                         0, 1, 0, 4, 3, b'd', b'o', b't', 0, 3, 0, 2, 3, 85,
+                         255];
+        let options = parse(&bytes).unwrap();
+        match options[0] {
+            EncryptedDns(ref instances) => assert_eq!(instances[0].adn, "r"),
+            ref o => panic!("unexpected option {:?}", o),
+        }
+        assert_eq!(encode(&options).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_encode_long_option() {
+        let name = String::from_utf8(vec![b'a'; 300]).unwrap();
+        let bytes = encode(&[HostName(name.clone())]).unwrap();
+        assert_eq!(bytes.len(), 2 + 255 + 2 + 45 + 1);
+        assert_eq!(&bytes[..2], &[12u8, 255][..]);
+        assert_eq!(&bytes[257..259], &[12u8, 45][..]);
+        assert_eq!(parse(&bytes).unwrap(), vec![HostName(name)]);
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        assert!(encode(&[Router(vec![IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))])]).is_err());
+    }
+}
@@ -0,0 +1,323 @@
+//! Location options: the civic address (option 99, RFC 4776) and
+//! coordinates (GeoConf option 123 and GeoLoc option 144, RFC 6225)
+
+use {Result, Error};
+use std::str;
+use num::{FromPrimitive};
+
+/// A civic (postal) address (RFC 4776)
+#[derive(Debug, PartialEq, Clone)]
+pub struct CivicAddress {
+    /// What the address is the location of, see `WHAT_*`
+    pub what: u8,
+    /// ISO 3166 country code, such as `"CH"`
+    pub country_code: String,
+    /// `(CAtype, CAvalue)` pairs, see `CATYPE_*`
+    pub elements: Vec<(u8, String)>,
+}
+
+pub const WHAT_DHCP_SERVER: u8 = 0;
+pub const WHAT_NETWORK_ELEMENT: u8 = 1;
+pub const WHAT_CLIENT: u8 = 2;
+
+// Some of the civic address element types, from the IANA "CAtypes"
+// registry
+pub const CATYPE_LANGUAGE: u8 = 0;
+/// National subdivision (state, canton, region)
+pub const CATYPE_A1: u8 = 1;
+/// County or district
+pub const CATYPE_A2: u8 = 2;
+/// City or township
+pub const CATYPE_A3: u8 = 3;
+pub const CATYPE_A4: u8 = 4;
+pub const CATYPE_A5: u8 = 5;
+pub const CATYPE_A6: u8 = 6;
+pub const CATYPE_HOUSE_NUMBER: u8 = 19;
+pub const CATYPE_HOUSE_NUMBER_SUFFIX: u8 = 20;
+pub const CATYPE_NAME: u8 = 23;
+pub const CATYPE_POSTAL_CODE: u8 = 24;
+pub const CATYPE_BUILDING: u8 = 25;
+pub const CATYPE_UNIT: u8 = 26;
+pub const CATYPE_FLOOR: u8 = 27;
+pub const CATYPE_ROOM: u8 = 28;
+/// Street name (RFC 5139)
+pub const CATYPE_RD: u8 = 34;
+pub const CATYPE_SCRIPT: u8 = 128;
+
+/// `[what, country-code(2), (catype, calength, cavalue...)...]`
+pub fn parse_civic_address(bytes: &[u8]) -> Option<CivicAddress> {
+    if bytes.len() < 3 || !bytes[1..3].iter().all(|c| (*c as char).is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut elements = vec![];
+    let mut rest = &bytes[3..];
+    while rest.len() > 0 {
+        if rest.len() < 2 || rest.len() < 2 + rest[1] as usize {
+            return None;
+        }
+        let end = 2 + rest[1] as usize;
+        let value = try_opt!(str::from_utf8(&rest[2..end]).ok());
+        elements.push((rest[0], value.to_owned()));
+        rest = &rest[end..];
+    }
+    Some(CivicAddress {
+        what: bytes[0],
+        country_code: try_opt!(str::from_utf8(&bytes[1..3]).ok()).to_owned(),
+        elements: elements,
+    })
+}
+
+/// Inverse of `parse_civic_address`
+pub fn encode_civic_address(address: &CivicAddress) -> Result<Vec<u8>> {
+    if address.country_code.len() != 2 || !address.country_code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(Error::EncodeError(format!("Invalid country code {:?}", address.country_code)));
+    }
+    let mut bytes = vec![address.what];
+    bytes.extend(address.country_code.as_bytes().iter().cloned());
+    for &(ca_type, ref value) in &address.elements {
+        if value.len() > 255 {
+            return Err(Error::EncodeError(format!("Civic address element {} is {} bytes long",
+                                                 ca_type, value.len())));
+        }
+        bytes.push(ca_type);
+        bytes.push(value.len() as u8);
+        bytes.extend(value.as_bytes().iter().cloned());
+    }
+    Ok(bytes)
+}
+
+enum_from_primitive! {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AltitudeType {
+    NoAltitude = 0,
+    Meters = 1,
+    Floors = 2,
+}
+}
+
+enum_from_primitive! {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Datum {
+    Wgs84 = 1,
+    /// NAD83 with NAVD88 heights
+    Nad83Navd88 = 2,
+    /// NAD83 with heights above mean lower low water
+    Nad83Mllw = 3,
+}
+}
+
+/// A position, as sent in GeoConf and GeoLoc (RFC 6225)
+///
+/// The uncertainty fields are the raw 6-bit codes. With `version` 1,
+/// use the `*_uncertainty_*` methods to turn them into degrees and
+/// metres. With `version` 0 they are RFC 3825 resolutions instead: the
+/// number of bits of the value that can be relied on.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Coordinates {
+    pub version: u8,
+    pub latitude_uncertainty: u8,
+    /// Degrees, positive north of the equator
+    pub latitude: f64,
+    pub longitude_uncertainty: u8,
+    /// Degrees, positive east of Greenwich
+    pub longitude: f64,
+    pub altitude_type: AltitudeType,
+    pub altitude_uncertainty: u8,
+    /// Metres or floors, depending on `altitude_type`
+    pub altitude: f64,
+    pub datum: Datum,
+}
+
+impl Coordinates {
+    /// `None` if the uncertainty isn't known
+    pub fn latitude_uncertainty_degrees(&self) -> Option<f64> {
+        self.uncertainty(self.latitude_uncertainty, 8)
+    }
+
+    /// `None` if the uncertainty isn't known
+    pub fn longitude_uncertainty_degrees(&self) -> Option<f64> {
+        self.uncertainty(self.longitude_uncertainty, 8)
+    }
+
+    /// `None` if the uncertainty isn't known, or the altitude isn't in
+    /// metres
+    pub fn altitude_uncertainty_meters(&self) -> Option<f64> {
+        if self.altitude_type != AltitudeType::Meters {
+            return None;
+        }
+        self.uncertainty(self.altitude_uncertainty, 21)
+    }
+
+    /// The uncertainty is `2^(bias - code)`, and 0 means unknown
+    fn uncertainty(&self, code: u8, bias: i32) -> Option<f64> {
+        if self.version != 1 || code == 0 {
+            return None;
+        }
+        Some(2f64.powi(bias - code as i32))
+    }
+}
+
+/// Fraction bits in the fixed-point fields
+const LATITUDE_FRACTION_BITS: u32 = 25;
+const ALTITUDE_FRACTION_BITS: u32 = 8;
+
+/// Decodes the 16 bytes of option 123 or 144
+pub fn parse_coordinates(bytes: &[u8]) -> Option<Coordinates> {
+    if bytes.len() != 16 {
+        return None;
+    }
+    let mut reader = BitReader { bits: bytes.iter().fold(0u128, |acc, b| acc << 8 | *b as u128), pos: 0 };
+    let latitude_uncertainty = reader.read(6) as u8;
+    let latitude = fixed_to_f64(reader.read(34), 34, LATITUDE_FRACTION_BITS);
+    let longitude_uncertainty = reader.read(6) as u8;
+    let longitude = fixed_to_f64(reader.read(34), 34, LATITUDE_FRACTION_BITS);
+    let altitude_type = try_opt!(AltitudeType::from_u64(reader.read(4)));
+    let altitude_uncertainty = reader.read(6) as u8;
+    let altitude = fixed_to_f64(reader.read(30), 30, ALTITUDE_FRACTION_BITS);
+    let version = reader.read(2) as u8;
+    let _reserved = reader.read(3);
+    let datum = try_opt!(Datum::from_u64(reader.read(3)));
+    if version > 1 || latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+        return None;
+    }
+    Some(Coordinates {
+        version: version,
+        latitude_uncertainty: latitude_uncertainty,
+        latitude: latitude,
+        longitude_uncertainty: longitude_uncertainty,
+        longitude: longitude,
+        altitude_type: altitude_type,
+        altitude_uncertainty: altitude_uncertainty,
+        altitude: altitude,
+        datum: datum,
+    })
+}
+
+/// Inverse of `parse_coordinates`. Values are rounded to the nearest
+/// step the fixed-point fields can hold.
+pub fn encode_coordinates(coordinates: &Coordinates) -> Result<Vec<u8>> {
+    let c = coordinates;
+    if c.version > 1 || c.latitude_uncertainty > 63 || c.longitude_uncertainty > 63 || c.altitude_uncertainty > 63 ||
+       !(c.latitude.abs() <= 90.0) || !(c.longitude.abs() <= 180.0) {
+        return Err(Error::EncodeError(format!("Invalid coordinates {:?}", c)));
+    }
+    let altitude = try!(f64_to_fixed(c.altitude, 30, ALTITUDE_FRACTION_BITS).ok_or_else(|| {
+        Error::EncodeError(format!("Altitude {} is out of range", c.altitude))
+    }));
+    let mut bits = 0u128;
+    {
+        let mut write = |value: u64, len: u32| bits = bits << len | (value & ((1 << len) - 1)) as u128;
+        write(c.latitude_uncertainty as u64, 6);
+        write(f64_to_fixed(c.latitude, 34, LATITUDE_FRACTION_BITS).unwrap(), 34);
+        write(c.longitude_uncertainty as u64, 6);
+        write(f64_to_fixed(c.longitude, 34, LATITUDE_FRACTION_BITS).unwrap(), 34);
+        write(c.altitude_type as u64, 4);
+        write(c.altitude_uncertainty as u64, 6);
+        write(altitude, 30);
+        write(c.version as u64, 2);
+        write(0, 3);
+        write(c.datum as u64, 3);
+    }
+    Ok((0..16).rev().map(|i| (bits >> (i * 8)) as u8).collect())
+}
+
+struct BitReader {
+    bits: u128,
+    pos: u32,
+}
+
+impl BitReader {
+    fn read(&mut self, len: u32) -> u64 {
+        self.pos += len;
+        ((self.bits >> (128 - self.pos)) & ((1 << len) - 1)) as u64
+    }
+}
+
+/// A `len`-bit two's complement number with `fraction` fraction bits
+fn fixed_to_f64(value: u64, len: u32, fraction: u32) -> f64 {
+    let signed = ((value << (64 - len)) as i64) >> (64 - len);
+    signed as f64 / (1u64 << fraction) as f64
+}
+
+/// Inverse of `fixed_to_f64`, or `None` if `value` doesn't fit
+fn f64_to_fixed(value: f64, len: u32, fraction: u32) -> Option<u64> {
+    let scaled = (value * (1u64 << fraction) as f64).round();
+    let limit = (1u64 << (len - 1)) as f64;
+    if !(scaled >= -limit && scaled < limit) {
+        return None;
+    }
+    Some(scaled as i64 as u64 & ((1 << len) - 1))
+}
+
+#[cfg(test)] mod tests {
+    use super::{CivicAddress, Coordinates, AltitudeType, Datum, parse_civic_address, encode_civic_address,
+                parse_coordinates, encode_coordinates, WHAT_CLIENT, CATYPE_A1, CATYPE_A3, CATYPE_RD};
+
+    #[test]
+    fn test_civic_address() {
+        let mut bytes = vec![2u8, b'C', b'H', 1, 2, b'Z', b'H', 3, 7];
+        bytes.extend("Zürich".as_bytes().iter().cloned());
+        bytes.extend(vec![34u8, 0].into_iter());
+        let address = parse_civic_address(&bytes).unwrap();
+        assert_eq!(address, CivicAddress {
+            what: WHAT_CLIENT,
+            country_code: "CH".to_owned(),
+            elements: vec![(CATYPE_A1, "ZH".to_owned()), (CATYPE_A3, "Zürich".to_owned()), (CATYPE_RD, "".to_owned())],
+        });
+        assert_eq!(encode_civic_address(&address).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_civic_address_malformed() {
+        assert_eq!(parse_civic_address(&[2u8, b'C']), None);
+        assert_eq!(parse_civic_address(&[2u8, b'C', b'1']), None);
+        assert_eq!(parse_civic_address(&[2u8, b'C', b'H', 1, 3, b'Z', b'H']), None);
+        assert_eq!(parse_civic_address(&[2u8, b'C', b'H', 1, 1, 0xff]), None);
+        assert!(encode_civic_address(&CivicAddress { what: 0, country_code: "CHE".to_owned(), elements: vec![] }).is_err());
+    }
+
+    #[test]
+    fn test_coordinates() {
+        let bytes = vec![0x48u8, 0x4d, 0x00, 0x00, 0x00, 0x4b, 0x65, 0x80, 0x00, 0x00, 0x15, 0x00, 0x00, 0x0f, 0xc0, 0x41];
+        let coordinates = parse_coordinates(&bytes).unwrap();
+        assert_eq!(coordinates, Coordinates {
+            version: 1,
+            latitude_uncertainty: 18,
+            latitude: 38.5,
+            longitude_uncertainty: 18,
+            longitude: -77.25,
+            altitude_type: AltitudeType::Meters,
+            altitude_uncertainty: 20,
+            altitude: 15.75,
+            datum: Datum::Wgs84,
+        });
+        assert_eq!(coordinates.latitude_uncertainty_degrees(), Some(1.0 / 1024.0));
+        assert_eq!(coordinates.altitude_uncertainty_meters(), Some(2.0));
+        assert_eq!(encode_coordinates(&coordinates).unwrap(), bytes);
+
+        let unknown = Coordinates { latitude_uncertainty: 0, version: 0, ..coordinates.clone() };
+        assert_eq!(unknown.latitude_uncertainty_degrees(), None);
+        assert_eq!(unknown.longitude_uncertainty_degrees(), None);
+
+        // rounded to the nearest 2^-25 of a degree
+        let rounded = Coordinates { latitude: 47.3769, longitude: 8.5417, ..coordinates.clone() };
+        let decoded = parse_coordinates(&encode_coordinates(&rounded).unwrap()).unwrap();
+        assert!((decoded.latitude - 47.3769).abs() < 1e-7 && (decoded.longitude - 8.5417).abs() < 1e-7);
+    }
+
+    #[test]
+    fn test_coordinates_malformed() {
+        let bytes = [0x48u8, 0x4d, 0x00, 0x00, 0x00, 0x4b, 0x65, 0x80, 0x00, 0x00, 0x15, 0x00, 0x00, 0x0f, 0xc0, 0x41];
+        assert_eq!(parse_coordinates(&bytes[..15]), None);
+        let mut bad_datum = bytes;
+        bad_datum[15] = 0x44;
+        assert_eq!(parse_coordinates(&bad_datum), None);
+        let mut bad_version = bytes;
+        bad_version[15] = 0x81;
+        assert_eq!(parse_coordinates(&bad_version), None);
+
+        let coordinates = parse_coordinates(&bytes).unwrap();
+        assert!(encode_coordinates(&Coordinates { latitude: 91.0, ..coordinates.clone() }).is_err());
+        assert!(encode_coordinates(&Coordinates { altitude: 3e6, ..coordinates.clone() }).is_err());
+    }
+}
@@ -1,10 +1,32 @@
 mod parse;
+mod encode;
+pub mod address_sharing;
+pub mod authentication;
+pub mod client_fqdn;
+pub mod relay_agent;
+pub mod classless_route;
+pub mod dns_name;
+pub mod dnr;
+pub mod geolocation;
+pub mod pxe;
+pub mod time_zone;
+pub mod vendor_class;
 
 use std::net::{IpAddr};
-pub use self::parse::parse;
+pub use self::parse::{parse, parse_at, parse_lenient_at, parse_areas, locate_option};
+pub use self::encode::{encode, encode_option};
+pub use self::authentication::{Authentication};
+pub use self::client_fqdn::{ClientFqdn, FqdnFlags};
+pub use self::relay_agent::{RelayAgentSubOption, VendorSpecificInformation};
+pub use self::classless_route::{Route};
+pub use self::vendor_class::{VendorClass};
+pub use self::pxe::{ClientArchitecture, Uuid, PxeSubOption};
+pub use self::dnr::{DnrInstance, SvcParam};
+pub use self::time_zone::{PosixTz};
+pub use self::address_sharing::{SixRd, PortParameters};
+pub use self::geolocation::{CivicAddress, Coordinates};
 
-#[allow(dead_code)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum DhcpOption {
     Pad,
     End,
@@ -74,13 +96,98 @@ pub enum DhcpOption {
     MaxMessageSize(u16),
     RenewalTimeValue(u32),
     RebindingTimeValue(u32),
-    ClassIdentifier,
-    ClientIdentifier,
+    ClassIdentifier(Vec<u8>),
+    /// `id_type` is a hardware type from `Htype`, or 0 when `id` is
+    /// something other than a hardware address
+    ClientIdentifier { id_type: u8, id: Vec<u8> },
+    NetWareIpDomain(String),
+    /// NetWare/IP sub-options (RFC 2242), as `(code, data)` pairs
+    NetWareIpInformation(Vec<(u8, Vec<u8>)>),
+    NisPlusDomain(String),
+    NisPlusServers(Vec<IpAddr>),
+    TftpServerName(String),
+    BootfileName(String),
+    MobileIpHomeAgent(Vec<IpAddr>),
+    SmtpServer(Vec<IpAddr>),
+    Pop3Server(Vec<IpAddr>),
+    NntpServer(Vec<IpAddr>),
+    WwwServer(Vec<IpAddr>),
+    FingerServer(Vec<IpAddr>),
+    IrcServer(Vec<IpAddr>),
+    StreetTalkServer(Vec<IpAddr>),
+    StdaServer(Vec<IpAddr>),
+
+    ClientFqdn(ClientFqdn),
+    RelayAgentInformation(Vec<RelayAgentSubOption>),
+    BcmcsControllerDomainNames(Vec<String>),
+    Authentication(Authentication),
+    /// Seconds since the client last talked to the server (RFC 4388)
+    ClientLastTransactionTime(u32),
+    /// All the addresses bound to the client (RFC 4388)
+    AssociatedIp(Vec<IpAddr>),
+    ClientSystemArchitecture(Vec<ClientArchitecture>),
+    ClientNetworkInterfaceIdentifier { interface_type: u8, major: u8, minor: u8 },
+    ClientMachineIdentifier(Uuid),
+    /// Civic address of the client, network element or server (RFC 4776)
+    CivicAddress(CivicAddress),
+    /// The local time zone as a POSIX TZ string (RFC 4833)
+    PosixTimeZone(PosixTz),
+    /// The local time zone as a tz database name, such as
+    /// `Europe/Zurich` (RFC 4833)
+    TzdbTimeZone(String),
+    /// Seconds an IPv6-capable client should stop using IPv4 for
+    /// (RFC 8925). See `MIN_V6ONLY_WAIT`.
+    Ipv6OnlyPreferred(u32),
+    /// Where to find the captive portal API (RFC 8910)
+    CaptivePortal(String),
+    /// Location as latitude, longitude and altitude (RFC 6225)
+    GeoConf(Coordinates),
+    DomainSearch(Vec<String>),
+    ClasslessStaticRoute(Vec<Route>),
+    VendorIdentifyingVendorClass(Vec<VendorClass>),
+    VendorIdentifyingVendorSpecific(Vec<VendorSpecificInformation>),
+    LostServer(String),
+    /// `GeoConf` under the code RFC 6225 added for it
+    GeoLoc(Coordinates),
+    /// The outcome of a (bulk or active) leasequery (RFC 6926)
+    StatusCode { code: StatusCodeType, message: String },
+    /// The server's current time, as seconds since the epoch (RFC 6926)
+    BaseTime(u32),
+    /// Seconds since the lease entered its current state (RFC 6926)
+    StartTimeOfState(u32),
+    /// Only return leases that changed at or after this time (RFC 6926)
+    QueryStartTime(u32),
+    /// Only return leases that changed at or before this time (RFC 6926)
+    QueryEndTime(u32),
+    DhcpState(DhcpStateType),
+    /// See `DATA_SOURCE_REMOTE` (RFC 6926)
+    DataSource(u8),
+    /// The ports this client may use on a shared IPv4 address (RFC 7618)
+    V4PortParams(PortParameters),
+    /// `CaptivePortal` under the code RFC 7710 first gave it. Polycom
+    /// phones also use this code, for provisioning URLs, so any URI is
+    /// accepted, and anything else is kept as `Unknown`.
+    LegacyCaptivePortal(String),
+    /// Manufacturer Usage Description URL (RFC 8520)
+    MudUrl(String),
+    /// Encrypted DNS resolvers (RFC 9463)
+    EncryptedDns(Vec<DnrInstance>),
+    SipUaConfigServiceDomains(Vec<String>),
+    /// 6rd IPv6 rapid deployment configuration (RFC 5969). See
+    /// `SixRd::delegated_prefix`.
+    SixRdConfig(SixRd),
+    AccessDomain(String),
+    /// Microsoft's pre-standard version of `ClasslessStaticRoute`
+    MsClasslessStaticRoute(Vec<Route>),
+
+    /// Any option this crate doesn't know how to decode, kept as-is
+    /// so that it can be encoded again byte-for-byte
+    Unknown { code: u8, data: Vec<u8> },
 }
 
 enum_from_primitive! {
-#[derive(Debug, PartialEq)]
-enum NodeType {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NodeType {
     B = 1,
     P = 2,
     M = 4,
@@ -89,25 +196,202 @@ enum NodeType {
 }
 
 enum_from_primitive! {
-#[derive(Debug, PartialEq)]
-enum OptionOverloadType {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OptionOverloadType {
     File = 1,
     Sname = 2,
     FileAndSname = 3,
 }
 }
 
-enum_from_primitive! {
-#[derive(Debug, PartialEq)]
-enum DhcpMessageTypes {
-    Discover = 1,
-    Offer = 2,
-    Request = 3,
-    Decline = 4,
-    Ack = 5,
-    Nak = 6,
-    Release = 7,
+/// The shortest time a client will honour in `Ipv6OnlyPreferred`;
+/// smaller values are treated as this (RFC 8925)
+pub const MIN_V6ONLY_WAIT: u32 = 300;
+
+/// The captive portal URI that means there is no captive portal
+/// (RFC 8910)
+pub const CAPTIVE_PORTAL_UNRESTRICTED: &'static str = "urn:ietf:params:capport:unrestricted";
+
+/// Lease states in the dhcp-state option (RFC 6926)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DhcpStateType {
+    Available,
+    Active,
+    Expired,
+    Released,
+    Abandoned,
+    Reset,
+    Remote,
+    Transitioning,
+    Unknown(u8),
+}
+
+impl DhcpStateType {
+    pub fn from_byte(byte: u8) -> DhcpStateType {
+        use self::DhcpStateType::*;
+        match byte {
+            1 => Available,
+            2 => Active,
+            3 => Expired,
+            4 => Released,
+            5 => Abandoned,
+            6 => Reset,
+            7 => Remote,
+            8 => Transitioning,
+            b => Unknown(b),
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        use self::DhcpStateType::*;
+        match *self {
+            Available => 1,
+            Active => 2,
+            Expired => 3,
+            Released => 4,
+            Abandoned => 5,
+            Reset => 6,
+            Remote => 7,
+            Transitioning => 8,
+            Unknown(b) => b,
+        }
+    }
+}
+
+/// Bit in the `DataSource` option that says the lease information came
+/// from a partner server rather than this one
+pub const DATA_SOURCE_REMOTE: u8 = 0x01;
+
+/// Leasequery status codes, as listed in the IANA "DHCP Status Code
+/// Type 151 Values" registry
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StatusCodeType {
+    Success,
+    UnspecFail,
+    QueryTerminated,
+    MalformedQuery,
+    NotAllowed,
+    /// RFC 7724
+    DataMissing,
+    ConnectionActive,
+    CatchUpComplete,
+    TlsConnectionRefused,
+    Unknown(u8),
+}
+
+impl StatusCodeType {
+    pub fn from_byte(byte: u8) -> StatusCodeType {
+        use self::StatusCodeType::*;
+        match byte {
+            0 => Success,
+            1 => UnspecFail,
+            2 => QueryTerminated,
+            3 => MalformedQuery,
+            4 => NotAllowed,
+            5 => DataMissing,
+            6 => ConnectionActive,
+            7 => CatchUpComplete,
+            8 => TlsConnectionRefused,
+            b => Unknown(b),
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        use self::StatusCodeType::*;
+        match *self {
+            Success => 0,
+            UnspecFail => 1,
+            QueryTerminated => 2,
+            MalformedQuery => 3,
+            NotAllowed => 4,
+            DataMissing => 5,
+            ConnectionActive => 6,
+            CatchUpComplete => 7,
+            TlsConnectionRefused => 8,
+            Unknown(b) => b,
+        }
+    }
 }
+
+/// DHCP message types, as listed in the IANA "Message Type 53 Values"
+/// registry
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DhcpMessageTypes {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+    /// RFC 3203
+    ForceRenew,
+    /// RFC 4388
+    LeaseQuery,
+    LeaseUnassigned,
+    LeaseUnknown,
+    LeaseActive,
+    /// RFC 6926
+    BulkLeaseQuery,
+    LeaseQueryDone,
+    /// RFC 7724
+    ActiveLeaseQuery,
+    LeaseQueryStatus,
+    Tls,
+    Unknown(u8),
+}
+
+impl DhcpMessageTypes {
+    pub fn from_byte(byte: u8) -> DhcpMessageTypes {
+        use self::DhcpMessageTypes::*;
+        match byte {
+            1 => Discover,
+            2 => Offer,
+            3 => Request,
+            4 => Decline,
+            5 => Ack,
+            6 => Nak,
+            7 => Release,
+            8 => Inform,
+            9 => ForceRenew,
+            10 => LeaseQuery,
+            11 => LeaseUnassigned,
+            12 => LeaseUnknown,
+            13 => LeaseActive,
+            14 => BulkLeaseQuery,
+            15 => LeaseQueryDone,
+            16 => ActiveLeaseQuery,
+            17 => LeaseQueryStatus,
+            18 => Tls,
+            b => Unknown(b),
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        use self::DhcpMessageTypes::*;
+        match *self {
+            Discover => 1,
+            Offer => 2,
+            Request => 3,
+            Decline => 4,
+            Ack => 5,
+            Nak => 6,
+            Release => 7,
+            Inform => 8,
+            ForceRenew => 9,
+            LeaseQuery => 10,
+            LeaseUnassigned => 11,
+            LeaseUnknown => 12,
+            LeaseActive => 13,
+            BulkLeaseQuery => 14,
+            LeaseQueryDone => 15,
+            ActiveLeaseQuery => 16,
+            LeaseQueryStatus => 17,
+            Tls => 18,
+            Unknown(b) => b,
+        }
+    }
 }
 
 //impl DhcpOption {
@@ -1,15 +1,232 @@
-use options::{DhcpOption};
+use options::{DhcpOption, DhcpMessageTypes, StatusCodeType, DhcpStateType, CAPTIVE_PORTAL_UNRESTRICTED, authentication, client_fqdn, relay_agent, classless_route, dns_name, vendor_class, pxe, dnr, time_zone, address_sharing, geolocation};
 use options::DhcpOption::*;
 use {Result, Error};
-use nom::{be_u8, be_u16, be_u32, be_i32, length_value, IResult, sized_buffer};
+use util::{uri_scheme, take_rest};
+use nom::{be_u8, be_u16, be_u32, be_i32, IResult, Err, ErrorKind};
 use std::borrow::{ToOwned};
 use std::str;
 use std::convert::{From};
 use std::net::{IpAddr, Ipv4Addr};
 use num::{FromPrimitive};
 
+/// Parses the options area that follows the magic cookie.
+///
+/// Options are returned in the order they appear on the wire. `Pad`
+/// bytes are skipped and parsing stops at the first `End`, so neither
+/// of those shows up in the result. Running out of input without an
+/// `End` is accepted, but an option whose length byte points past the
+/// end of the input is an error, as is anything but `Pad` after `End`.
 pub fn parse(bytes: &[u8]) -> Result<Vec<DhcpOption>> {
-    Ok(vec![])
+    parse_at(bytes, 0)
+}
+
+/// Like `parse`, but for an options area that starts `offset` bytes
+/// into the packet, so that errors point at the right place.
+pub fn parse_at(bytes: &[u8], offset: usize) -> Result<Vec<DhcpOption>> {
+    let (options, errors) = parse_areas(&[(bytes, offset)]);
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(options),
+    }
+}
+
+/// Like `parse_at`, but skips over options it can't decode instead of
+/// failing. Every option that could be decoded is returned, along with
+/// an error for each part of the input that was skipped.
+pub fn parse_lenient_at(bytes: &[u8], offset: usize) -> (Vec<DhcpOption>, Vec<Error>) {
+    parse_areas(&[(bytes, offset)])
+}
+
+/// Like `parse_lenient_at`, for options spread over several areas of a
+/// packet (see option 52), given as `(bytes, offset)` pairs. They have
+/// to be in the order RFC 3396 concatenates them in: the options area,
+/// then `file`, then `sname`.
+///
+/// Options that turn up more than once, in the same area or not, have
+/// their data joined up before being decoded. Errors are sorted by
+/// area, and by offset within each area.
+pub fn parse_areas(areas: &[(&[u8], usize)]) -> (Vec<DhcpOption>, Vec<Error>) {
+    let mut raw_options = vec![];
+    let mut errors = vec![];
+    for (area, &(bytes, offset)) in areas.iter().enumerate() {
+        let (raw, errs) = split_area(area, bytes, offset);
+        raw_options.extend(raw.into_iter());
+        errors.extend(errs.into_iter().map(|e| (area, e)));
+    }
+    let mut options = vec![];
+    for raw in concatenate(raw_options) {
+        match decode(&raw) {
+            Ok(o) => options.push(o),
+            Err(e) => errors.push((raw.area, e)),
+        }
+    }
+    errors.sort_by(|&(a, ref e), &(b, ref f)| (a, e.offset()).cmp(&(b, f.offset())));
+    (options, errors.into_iter().map(|(_, e)| e).collect())
+}
+
+/// The data of every instance of option `code` in `areas`, joined up
+/// the way `parse_areas` does it, along with the offset in the packet
+/// of each of its bytes. Nothing is decoded, so this works even when
+/// other options are broken.
+pub fn locate_option(areas: &[(&[u8], usize)], code: u8) -> (Vec<u8>, Vec<usize>) {
+    let mut data = vec![];
+    let mut offsets = vec![];
+    for (area, &(bytes, offset)) in areas.iter().enumerate() {
+        for raw in split_area(area, bytes, offset).0.into_iter().filter(|o| o.code == code) {
+            offsets.extend((0..raw.data.len()).map(|i| raw.offset + 2 + i));
+            data.extend(raw.data.into_iter());
+        }
+    }
+    (data, offsets)
+}
+
+/// An option as it was found on the wire, before decoding its data
+struct RawOption {
+    /// which of the areas passed to `parse_areas` it was found in
+    area: usize,
+    /// where the (first instance of the) option starts in the packet
+    offset: usize,
+    code: u8,
+    data: Vec<u8>,
+}
+
+/// Finds the `[code, length, data...]` options in an options area
+fn split_area(area: usize, bytes: &[u8], offset: usize) -> (Vec<RawOption>, Vec<Error>) {
+    let mut options = vec![];
+    let mut errors = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let code = bytes[pos];
+        if code == 0u8 {
+            pos += 1;
+            continue;
+        }
+        if code == 255u8 {
+            pos += 1;
+            if let Some(p) = bytes[pos..].iter().position(|b| *b != 0u8) {
+                errors.push(Error::LeftoverInput { offset: offset + pos + p });
+            }
+            break;
+        }
+        if bytes.len() < pos + 2 || bytes.len() < pos + 2 + bytes[pos + 1] as usize {
+            // there's no telling where the next option would start
+            errors.push(Error::OptionLengthMismatch { offset: offset + pos, code: code });
+            break;
+        }
+        let end = pos + 2 + bytes[pos + 1] as usize;
+        options.push(RawOption {
+            area: area,
+            offset: offset + pos,
+            code: code,
+            data: bytes[pos + 2..end].to_vec(),
+        });
+        pos = end;
+    }
+    (options, errors)
+}
+
+/// Appends the data of every later instance of an option onto its
+/// first instance, as RFC 3396 asks
+fn concatenate(options: Vec<RawOption>) -> Vec<RawOption> {
+    let mut joined: Vec<RawOption> = vec![];
+    for raw in options.into_iter() {
+        if let Some(first) = joined.iter_mut().filter(|o| o.code == raw.code).next() {
+            first.data.extend(raw.data.into_iter());
+            continue;
+        }
+        joined.push(raw);
+    }
+    joined
+}
+
+/// Decodes a single option's data, whatever its length
+fn decode(raw: &RawOption) -> Result<DhcpOption> {
+    let (offset, code) = (raw.offset, raw.code);
+    // the parsers below take the code followed by all of the data
+    let mut bytes = vec![code];
+    bytes.extend(raw.data.iter().cloned());
+    match dhcp_option(&bytes) {
+        IResult::Done(i, o) => {
+            if i.len() > 0 {
+                Err(Error::OptionLengthMismatch { offset: offset, code: code })
+            } else {
+                Ok(o)
+            }
+        },
+        IResult::Error(Err::Position(ErrorKind::Switch, _)) => {
+            Ok(Unknown { code: code, data: raw.data.clone() })
+        },
+        IResult::Error(Err::NodePosition(ErrorKind::Switch, _, ref e)) if is_bad_string(e) => {
+            Err(Error::InvalidUtf8 { offset: offset, code: code })
+        },
+        IResult::Error(_) => {
+            Err(Error::InvalidOptionValue { offset: offset, code: code })
+        },
+        IResult::Incomplete(_) => {
+            Err(Error::OptionLengthMismatch { offset: offset, code: code })
+        }
+    }
+}
+
+/// Whether a parser failed on `str::from_utf8`
+fn is_bad_string(e: &Err<&[u8]>) -> bool {
+    match *e {
+        Err::Position(ErrorKind::MapRes, _) => true,
+        _ => false,
+    }
+}
+
+/// Splits up data made of `[code, length, data...]` sub-options, as
+/// used inside options like 63. Returns `None` if the last sub-option
+/// runs past the end of `bytes`.
+pub fn sub_options(bytes: &[u8]) -> Option<Vec<(u8, Vec<u8>)>> {
+    let mut subs = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        if rest.len() < 2 || rest.len() < 2 + rest[1] as usize {
+            return None;
+        }
+        let end = 2 + rest[1] as usize;
+        subs.push((rest[0], rest[2..end].to_vec()));
+        rest = &rest[end..];
+    }
+    Some(subs)
+}
+
+fn client_identifier_from_bytes(bytes: &[u8]) -> Option<DhcpOption> {
+    if bytes.len() < 2 {
+        return None;
+    }
+    Some(ClientIdentifier { id_type: bytes[0], id: bytes[1..].to_vec() })
+}
+
+/// The captive portal URI has to be HTTPS, unless it says there is no
+/// captive portal (RFC 8910)
+fn captive_portal_uri(bytes: &[u8]) -> Option<String> {
+    let uri = try_opt!(str::from_utf8(bytes).ok());
+    if uri != CAPTIVE_PORTAL_UNRESTRICTED && !try_opt!(uri_scheme(uri)).eq_ignore_ascii_case("https") {
+        return None;
+    }
+    Some(uri.to_owned())
+}
+
+fn any_uri(bytes: &[u8]) -> Option<String> {
+    let uri = try_opt!(str::from_utf8(bytes).ok());
+    try_opt!(uri_scheme(uri));
+    Some(uri.to_owned())
+}
+
+fn posix_tz(bytes: &[u8]) -> Option<time_zone::PosixTz> {
+    time_zone::PosixTz::parse(try_opt!(str::from_utf8(bytes).ok()))
+}
+
+/// MUD URLs have to be HTTPS (RFC 8520)
+fn mud_url(bytes: &[u8]) -> Option<String> {
+    let url = try_opt!(str::from_utf8(bytes).ok());
+    if !try_opt!(uri_scheme(url)).eq_ignore_ascii_case("https") {
+        return None;
+    }
+    Some(url.to_owned())
 }
 
 fn u32_to_ip(a: u32) -> IpAddr {
@@ -32,26 +249,12 @@ fn ip_addr_pairs(addrs: Vec<u32>) -> Vec<(IpAddr, IpAddr)> {
        .collect()
 }
 
-fn num_u16s(bytes: &[u8]) -> IResult<&[u8], u8> {
-    match be_u8(bytes) {
-        IResult::Done(i, o) => IResult::Done(i, o / 2),
-        a => a,
-    }
-}
-
-fn num_u32s(bytes: &[u8]) -> IResult<&[u8], u8> {
-    match be_u8(bytes) {
-        IResult::Done(i, o) => IResult::Done(i, o / 4),
-        a => a,
-    }
-}
-
 macro_rules! ip_pairs(
     ($name:ident, $tag:expr, $variant:expr) => (
         named!($name<&[u8], DhcpOption>,
             chain!(
                 tag!([$tag]) ~
-                addrs: length_value!(num_u32s, be_u32),
+                addrs: many0!(be_u32),
                 || { $variant(ip_addr_pairs(addrs)) }
             )
         );
@@ -69,7 +272,7 @@ macro_rules! many_ips(
         named!($name<&[u8], DhcpOption>,
             chain!(
                 tag!([$tag]) ~
-                addrs: length_value!(num_u32s, be_u32),
+                addrs: many0!(be_u32),
                 || { $variant(many_ip_addrs(addrs)) }
             )
         );
@@ -89,7 +292,7 @@ macro_rules! length_specific_string(
         named!($name<&[u8], DhcpOption>,
             chain!(
                 tag!([$tag]) ~
-                s: map_res!(sized_buffer, str::from_utf8),
+                s: map_res!(take_rest, str::from_utf8),
                 || { $variant(s.to_owned()) }
             )
         );
@@ -101,7 +304,6 @@ macro_rules! single_ip(
         named!($name<&[u8], DhcpOption>,
             chain!(
                 tag!([$tag]) ~
-                _length: be_u8 ~
                 addr: be_u32,
                 || { $variant(u32_to_ip(addr)) }
             )
@@ -109,12 +311,23 @@ macro_rules! single_ip(
     )
 );
 
+macro_rules! single_u32(
+    ($name:ident, $tag:expr, $variant:expr) => (
+        named!($name<&[u8], DhcpOption>,
+            chain!(
+                tag!([$tag]) ~
+                val: be_u32,
+                || { $variant(val) }
+            )
+        );
+    )
+);
+
 macro_rules! bool(
     ($name:ident, $tag:expr, $variant:expr) => (
         named!($name<&[u8], DhcpOption>,
             chain!(
                 tag!([$tag]) ~
-                _length: be_u8 ~
                 val: be_u8,
                 || { $variant(val == 1u8) }
             )
@@ -127,7 +340,6 @@ macro_rules! from_primitive(
         named!($name<&[u8], DhcpOption>,
             chain!(
                 tag!([$tag]) ~
-                _l: be_u8 ~
                 data: map_opt!(be_u8, FromPrimitive::from_u8),
                 || { $variant(data) }
             )
@@ -140,8 +352,6 @@ single_ip!(subnet_mask, 1u8, SubnetMask);
 named!(time_offset<&[u8], DhcpOption>,
     chain!(
         tag!([2u8]) ~
-        // length field, always 4
-        be_u8 ~
         time: be_i32,
         || { TimeOffset(time) }
     )
@@ -162,7 +372,6 @@ length_specific_string!(hostname, 12u8, HostName);
 named!(boot_file_size<&[u8], DhcpOption>,
     chain!(
         tag!([13u8]) ~
-        _length: be_u8 ~
         s: be_u16,
         || { BootFileSize(s) }
     )
@@ -175,49 +384,36 @@ length_specific_string!(root_path, 17u8, RootPath);
 length_specific_string!(extensions_path, 18u8, ExtensionsPath);
 
 // COLLECT ALL OF THE ABOVE INTO ONE PARSER
-named!(vendor_extensions_rfc1497<&[u8], DhcpOption>, alt!(
-          chain!(tag!([0u8]),
-              || { Pad }
-          )
-        | chain!(
-                tag!([255u8]),
-                || { End }
-            )
-        | subnet_mask
-        | time_offset
-        | router
-        | time_server
-        | name_server         // 5
-        | domain_name_server
-        | log_server
-        | cookie_server
-        | lpr_server
-        | impress_server      // 10
-        | resource_loc_server
-        | hostname
-        | boot_file_size
-        | merit_dump_file
-        | domain_name         // 15
-        | swap_server
-        | root_path
-        | extensions_path
+named!(vendor_extensions_rfc1497<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          0u8 => chain!(tag!([0u8]), || { Pad })
+        | 255u8 => chain!(tag!([255u8]), || { End })
+        | 1u8 => call!(subnet_mask)
+        | 2u8 => call!(time_offset)
+        | 3u8 => call!(router)
+        | 4u8 => call!(time_server)
+        | 5u8 => call!(name_server)
+        | 6u8 => call!(domain_name_server)
+        | 7u8 => call!(log_server)
+        | 8u8 => call!(cookie_server)
+        | 9u8 => call!(lpr_server)
+        | 10u8 => call!(impress_server)
+        | 11u8 => call!(resource_loc_server)
+        | 12u8 => call!(hostname)
+        | 13u8 => call!(boot_file_size)
+        | 14u8 => call!(merit_dump_file)
+        | 15u8 => call!(domain_name)
+        | 16u8 => call!(swap_server)
+        | 17u8 => call!(root_path)
+        | 18u8 => call!(extensions_path)
     )
 );
 
 bool!(ip_forwarding, 19u8, IPForwarding);
 bool!(non_source_local_routing, 20u8, NonLocalSourceRouting);
-// TODO
-/* named!(policy_filter<&[u8], DhcpOption>, */
-/*     chain!( */
-/*         tag!([21u8]) ~ */
-/*         s: map!(sized_buffer, ip_addr_pairs), */
-/*         || { PolicyFilter(s) } */
-/*     ) */
-/* ); */
+ip_pairs!(policy_filter, 21u8, PolicyFilter);
 named!(max_datagram_reassembly_size<&[u8], DhcpOption>,
     chain!(
         tag!([22u8]) ~
-        _len: be_u8 ~
         aa: be_u16,
         || { MaxDatagramReassemblySize(aa) }
     )
@@ -225,7 +421,6 @@ named!(max_datagram_reassembly_size<&[u8], DhcpOption>,
 named!(default_ip_ttl<&[u8], DhcpOption>,
     chain!(
         tag!([23u8]) ~
-        _length: be_u8 ~
         ttl: be_u8,
         || { DefaultIpTtl(ttl) }
     )
@@ -233,7 +428,6 @@ named!(default_ip_ttl<&[u8], DhcpOption>,
 named!(path_mtu_aging_timeout<&[u8], DhcpOption>,
     chain!(
         tag!([24u8]) ~
-        _length: be_u8 ~
         timeout: be_u32,
         || { PathMtuAgingTimeout(timeout) }
     )
@@ -241,27 +435,26 @@ named!(path_mtu_aging_timeout<&[u8], DhcpOption>,
 named!(path_mtu_plateau_table<&[u8], DhcpOption>,
     chain!(
         tag!([25u8]) ~
-        sizes: length_value!(num_u16s, be_u16),
+        sizes: many0!(be_u16),
         || { PathMtuPlateauTable(sizes) }
     )
 );
 
 // COLLECT
-named!(ip_layer_parameters_per_host<&[u8], DhcpOption>, alt!(
-          ip_forwarding
-        | non_source_local_routing      // 20
-        /* | policy_filter //TODO */
-        | max_datagram_reassembly_size
-        | default_ip_ttl
-        | path_mtu_aging_timeout
-        | path_mtu_plateau_table        // 25
+named!(ip_layer_parameters_per_host<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          19u8 => call!(ip_forwarding)
+        | 20u8 => call!(non_source_local_routing)
+        | 21u8 => call!(policy_filter)
+        | 22u8 => call!(max_datagram_reassembly_size)
+        | 23u8 => call!(default_ip_ttl)
+        | 24u8 => call!(path_mtu_aging_timeout)
+        | 25u8 => call!(path_mtu_plateau_table)
     )
 );
 
 named!(interface_mtu<&[u8], DhcpOption>,
     chain!(
         tag!([26u8]) ~
-        _length: be_u8 ~
         mtu: be_u16,
         || { InterfaceMtu(mtu) }
     )
@@ -275,15 +468,15 @@ single_ip!(router_solicitation_address, 32u8, RouterSolicitationAddress);
 ip_pairs!(static_route, 33u8, StaticRoute);
 
 // COLLECT
-named!(ip_layer_parameters_per_interface<&[u8], DhcpOption>, alt!(
-          interface_mtu
-        | all_subnets_are_local
-        | broadcast_address
-        | perform_mask_discovery
-        | mask_supplier                 // 30
-        | perform_router_discovery
-        | router_solicitation_address
-        | static_route
+named!(ip_layer_parameters_per_interface<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          26u8 => call!(interface_mtu)
+        | 27u8 => call!(all_subnets_are_local)
+        | 28u8 => call!(broadcast_address)
+        | 29u8 => call!(perform_mask_discovery)
+        | 30u8 => call!(mask_supplier)
+        | 31u8 => call!(perform_router_discovery)
+        | 32u8 => call!(router_solicitation_address)
+        | 33u8 => call!(static_route)
     )
 );
 
@@ -291,7 +484,6 @@ bool!(trailer_encapsulation, 34u8, TrailerEncapsulation);
 named!(arp_cache_timeout<&[u8], DhcpOption>,
     chain!(
         tag!([35u8]) ~
-        _length: be_u8 ~
         timeout: be_u32,
         || { ArpCacheTimeout(timeout) }
     )
@@ -299,17 +491,16 @@ named!(arp_cache_timeout<&[u8], DhcpOption>,
 bool!(ethernet_encapsulation, 36u8, EthernetEncapsulation);
 
 // COLLECT
-named!(link_layer_parameters_per_interface<&[u8], DhcpOption>, alt!(
-          trailer_encapsulation
-        | arp_cache_timeout         // 35
-        | ethernet_encapsulation
+named!(link_layer_parameters_per_interface<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          34u8 => call!(trailer_encapsulation)
+        | 35u8 => call!(arp_cache_timeout)
+        | 36u8 => call!(ethernet_encapsulation)
     )
 );
 
 named!(tcp_default_ttl<&[u8], DhcpOption>,
     chain!(
         tag!([37u8]) ~
-        _length: be_u8 ~
         ttl: be_u8,
         || { TcpDefaultTtl(ttl) }
     )
@@ -317,7 +508,6 @@ named!(tcp_default_ttl<&[u8], DhcpOption>,
 named!(tcp_keepalive_interval<&[u8], DhcpOption>,
     chain!(
         tag!([38u8]) ~
-        _length: be_u8 ~
         interval: be_u32,
         || { TcpKeepaliveInterval(interval) }
     )
@@ -325,10 +515,10 @@ named!(tcp_keepalive_interval<&[u8], DhcpOption>,
 bool!(tcp_keepalive_garbage, 39u8, TcpKeepaliveGarbage);
 
 // COLLECT
-named!(tcp_parameters<&[u8], DhcpOption>, alt!(
-          tcp_default_ttl
-        | tcp_keepalive_interval
-        | tcp_keepalive_garbage
+named!(tcp_parameters<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          37u8 => call!(tcp_default_ttl)
+        | 38u8 => call!(tcp_keepalive_interval)
+        | 39u8 => call!(tcp_keepalive_garbage)
     )
 );
 
@@ -338,8 +528,8 @@ many_ips!(ntp_servers, 42u8, NtpServers);
 named!(vendor_extensions<&[u8], DhcpOption>,
     chain!(
         tag!([43u8]) ~
-        bytes: length_value!(be_u8, be_u8),
-        || { VendorExtensions(bytes) }
+        bytes: take_rest,
+        || { VendorExtensions(bytes.to_vec()) }
     )
 );
 many_ips!(net_bios_name_servers, 44u8, NetBiosNameServers);
@@ -347,7 +537,6 @@ many_ips!(net_bios_datagram_distribution_server, 45u8, NetBiosDatagramDistributi
 named!(net_bios_node_type<&[u8], DhcpOption>,
     chain!(
         tag!([46u8]) ~
-        _length: be_u8 ~
         data: map_opt!(be_u8, FromPrimitive::from_u8),
         || { NetBiosNodeType(data) }
     )
@@ -357,17 +546,17 @@ many_ips!(xfont_server, 48u8, XFontServer);
 many_ips!(xdisplay_manager, 49u8, XDisplayManager);
 
 // COLLECT
-named!(application_and_service_parameters<&[u8], DhcpOption>, alt!(
-          nis_domain                            // 40
-        | network_information_servers
-        | ntp_servers
-        | vendor_extensions
-        | net_bios_name_servers
-        | net_bios_datagram_distribution_server // 45
-        | net_bios_node_type
-        | net_bios_scope
-        | xfont_server
-        | xdisplay_manager
+named!(application_and_service_parameters<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          40u8 => call!(nis_domain)
+        | 41u8 => call!(network_information_servers)
+        | 42u8 => call!(ntp_servers)
+        | 43u8 => call!(vendor_extensions)
+        | 44u8 => call!(net_bios_name_servers)
+        | 45u8 => call!(net_bios_datagram_distribution_server)
+        | 46u8 => call!(net_bios_node_type)
+        | 47u8 => call!(net_bios_scope)
+        | 48u8 => call!(xfont_server)
+        | 49u8 => call!(xdisplay_manager)
     )
 );
 
@@ -375,70 +564,421 @@ single_ip!(requested_ip_address, 50u8, RequestedIpAddress);
 named!(ip_address_lease_time<&[u8], DhcpOption>,
     chain!(
         tag!([51u8]) ~
-        _length: be_u8 ~
         time: be_u32,
         || { IpAddressLeaseTime(time) }
     )
 );
 from_primitive!(option_overload, 52u8, OptionOverload);
-from_primitive!(message_type, 53u8, MessageType);
+named!(message_type<&[u8], DhcpOption>,
+    chain!(
+        tag!([53u8]) ~
+        data: map!(be_u8, DhcpMessageTypes::from_byte),
+        || { MessageType(data) }
+    )
+);
 single_ip!(server_identifier, 54u8, ServerIdentifier);
 named!(param_request_list<&[u8], DhcpOption>,
     chain!(
         tag!([55u8]) ~
-        data: length_value!(be_u8, be_u8),
-        || { ParamRequestList(data) }
+        data: take_rest,
+        || { ParamRequestList(data.to_vec()) }
     )
 );
 length_specific_string!(message, 56u8, Message);
 named!(max_message_size<&[u8], DhcpOption>,
     chain!(
         tag!([57u8]) ~
-        _l: be_u8 ~
         size_: be_u16,
         || { MaxMessageSize(size_) }
     )
 );
+named!(renewal_time_value<&[u8], DhcpOption>,
+    chain!(
+        tag!([58u8]) ~
+        time: be_u32,
+        || { RenewalTimeValue(time) }
+    )
+);
+named!(rebinding_time_value<&[u8], DhcpOption>,
+    chain!(
+        tag!([59u8]) ~
+        time: be_u32,
+        || { RebindingTimeValue(time) }
+    )
+);
+named!(class_identifier<&[u8], DhcpOption>,
+    chain!(
+        tag!([60u8]) ~
+        data: take_rest,
+        || { ClassIdentifier(data.to_vec()) }
+    )
+);
+named!(client_identifier<&[u8], DhcpOption>,
+    chain!(
+        tag!([61u8]) ~
+        id: map_opt!(take_rest, client_identifier_from_bytes),
+        || { id }
+    )
+);
+
+// COLLECT
+named!(dhcp_extensions<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          50u8 => call!(requested_ip_address)
+        | 51u8 => call!(ip_address_lease_time)
+        | 52u8 => call!(option_overload)
+        | 53u8 => call!(message_type)
+        | 54u8 => call!(server_identifier)
+        | 55u8 => call!(param_request_list)
+        | 56u8 => call!(message)
+        | 57u8 => call!(max_message_size)
+        | 58u8 => call!(renewal_time_value)
+        | 59u8 => call!(rebinding_time_value)
+        | 60u8 => call!(class_identifier)
+        | 61u8 => call!(client_identifier)
+    )
+);
+
+length_specific_string!(netware_ip_domain, 62u8, NetWareIpDomain);
+named!(netware_ip_information<&[u8], DhcpOption>,
+    chain!(
+        tag!([63u8]) ~
+        subs: map_opt!(take_rest, sub_options),
+        || { NetWareIpInformation(subs) }
+    )
+);
+length_specific_string!(nis_plus_domain, 64u8, NisPlusDomain);
+many_ips!(nis_plus_servers, 65u8, NisPlusServers);
+length_specific_string!(tftp_server_name, 66u8, TftpServerName);
+length_specific_string!(bootfile_name, 67u8, BootfileName);
+many_ips!(mobile_ip_home_agent, 68u8, MobileIpHomeAgent);
+many_ips!(smtp_server, 69u8, SmtpServer);
+many_ips!(pop3_server, 70u8, Pop3Server);
+many_ips!(nntp_server, 71u8, NntpServer);
+many_ips!(www_server, 72u8, WwwServer);
+many_ips!(finger_server, 73u8, FingerServer);
+many_ips!(irc_server, 74u8, IrcServer);
+many_ips!(street_talk_server, 75u8, StreetTalkServer);
+many_ips!(stda_server, 76u8, StdaServer);
+
+// COLLECT
+named!(more_application_and_service_parameters<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          62u8 => call!(netware_ip_domain)
+        | 63u8 => call!(netware_ip_information)
+        | 64u8 => call!(nis_plus_domain)
+        | 65u8 => call!(nis_plus_servers)
+        | 66u8 => call!(tftp_server_name)
+        | 67u8 => call!(bootfile_name)
+        | 68u8 => call!(mobile_ip_home_agent)
+        | 69u8 => call!(smtp_server)
+        | 70u8 => call!(pop3_server)
+        | 71u8 => call!(nntp_server)
+        | 72u8 => call!(www_server)
+        | 73u8 => call!(finger_server)
+        | 74u8 => call!(irc_server)
+        | 75u8 => call!(street_talk_server)
+        | 76u8 => call!(stda_server)
+    )
+);
+
+named!(client_fqdn<&[u8], DhcpOption>,
+    chain!(
+        tag!([81u8]) ~
+        fqdn: map_opt!(take_rest, client_fqdn::parse),
+        || { ClientFqdn(fqdn) }
+    )
+);
+
+named!(relay_agent_information<&[u8], DhcpOption>,
+    chain!(
+        tag!([82u8]) ~
+        subs: map_opt!(take_rest, relay_agent::parse),
+        || { RelayAgentInformation(subs) }
+    )
+);
+named!(classless_static_route<&[u8], DhcpOption>,
+    chain!(
+        tag!([121u8]) ~
+        routes: map_opt!(take_rest, classless_route::parse),
+        || { ClasslessStaticRoute(routes) }
+    )
+);
+named!(ms_classless_static_route<&[u8], DhcpOption>,
+    chain!(
+        tag!([249u8]) ~
+        routes: map_opt!(take_rest, classless_route::parse),
+        || { MsClasslessStaticRoute(routes) }
+    )
+);
+
+/// A macro for options that hold a list of domain names in DNS wire
+/// format
+macro_rules! domain_names(
+    ($name:ident, $tag:expr, $variant:expr) => (
+        named!($name<&[u8], DhcpOption>,
+            chain!(
+                tag!([$tag]) ~
+                names: map_opt!(take_rest, dns_name::parse_names),
+                || { $variant(names) }
+            )
+        );
+    )
+);
+
+/// Like `domain_names`, for options that hold exactly one name
+macro_rules! single_domain_name(
+    ($name:ident, $tag:expr, $variant:expr) => (
+        named!($name<&[u8], DhcpOption>,
+            chain!(
+                tag!([$tag]) ~
+                name: map_opt!(take_rest, dns_name::parse_single_name),
+                || { $variant(name) }
+            )
+        );
+    )
+);
+
+named!(vendor_identifying_vendor_class<&[u8], DhcpOption>,
+    chain!(
+        tag!([124u8]) ~
+        classes: map_opt!(take_rest, vendor_class::parse),
+        || { VendorIdentifyingVendorClass(classes) }
+    )
+);
+named!(vendor_identifying_vendor_specific<&[u8], DhcpOption>,
+    chain!(
+        tag!([125u8]) ~
+        blocks: map_opt!(take_rest, relay_agent::vendor_specific_information),
+        || { VendorIdentifyingVendorSpecific(blocks) }
+    )
+);
+
+domain_names!(bcmcs_controller_domain_names, 88u8, BcmcsControllerDomainNames);
+named!(authentication<&[u8], DhcpOption>,
+    chain!(
+        tag!([90u8]) ~
+        auth: map_opt!(take_rest, authentication::parse),
+        || { Authentication(auth) }
+    )
+);
+single_u32!(client_last_transaction_time, 91u8, ClientLastTransactionTime);
+many_ips!(associated_ip, 92u8, AssociatedIp);
+named!(client_system_architecture<&[u8], DhcpOption>,
+    chain!(
+        tag!([93u8]) ~
+        archs: map_opt!(take_rest, pxe::parse_architectures),
+        || { ClientSystemArchitecture(archs) }
+    )
+);
+named!(client_network_interface_identifier<&[u8], DhcpOption>,
+    chain!(
+        tag!([94u8]) ~
+        interface_type: be_u8 ~
+        major: be_u8 ~
+        minor: be_u8,
+        || { ClientNetworkInterfaceIdentifier { interface_type: interface_type, major: major, minor: minor } }
+    )
+);
+named!(client_machine_identifier<&[u8], DhcpOption>,
+    chain!(
+        tag!([97u8]) ~
+        uuid: map_opt!(take_rest, pxe::parse_uuid),
+        || { ClientMachineIdentifier(uuid) }
+    )
+);
+named!(civic_address<&[u8], DhcpOption>,
+    chain!(
+        tag!([99u8]) ~
+        address: map_opt!(take_rest, geolocation::parse_civic_address),
+        || { CivicAddress(address) }
+    )
+);
+named!(posix_time_zone<&[u8], DhcpOption>,
+    chain!(
+        tag!([100u8]) ~
+        tz: map_opt!(take_rest, posix_tz),
+        || { PosixTimeZone(tz) }
+    )
+);
+named!(tzdb_time_zone<&[u8], DhcpOption>,
+    chain!(
+        tag!([101u8]) ~
+        name: map_opt!(take_rest, time_zone::parse_tzdb_name),
+        || { TzdbTimeZone(name) }
+    )
+);
+single_u32!(ipv6_only_preferred, 108u8, Ipv6OnlyPreferred);
+named!(captive_portal<&[u8], DhcpOption>,
+    chain!(
+        tag!([114u8]) ~
+        uri: map_opt!(take_rest, captive_portal_uri),
+        || { CaptivePortal(uri) }
+    )
+);
+domain_names!(domain_search, 119u8, DomainSearch);
+named!(geo_conf<&[u8], DhcpOption>,
+    chain!(
+        tag!([123u8]) ~
+        coordinates: map_opt!(take_rest, geolocation::parse_coordinates),
+        || { GeoConf(coordinates) }
+    )
+);
+named!(geo_loc<&[u8], DhcpOption>,
+    chain!(
+        tag!([144u8]) ~
+        coordinates: map_opt!(take_rest, geolocation::parse_coordinates),
+        || { GeoLoc(coordinates) }
+    )
+);
+single_domain_name!(lost_server, 137u8, LostServer);
+named!(status_code<&[u8], DhcpOption>,
+    chain!(
+        tag!([151u8]) ~
+        code: map!(be_u8, StatusCodeType::from_byte) ~
+        message: map_res!(take_rest, str::from_utf8),
+        || { StatusCode { code: code, message: message.to_owned() } }
+    )
+);
+single_u32!(base_time, 152u8, BaseTime);
+single_u32!(start_time_of_state, 153u8, StartTimeOfState);
+single_u32!(query_start_time, 154u8, QueryStartTime);
+single_u32!(query_end_time, 155u8, QueryEndTime);
+named!(dhcp_state<&[u8], DhcpOption>,
+    chain!(
+        tag!([156u8]) ~
+        state: map!(be_u8, DhcpStateType::from_byte),
+        || { DhcpState(state) }
+    )
+);
+named!(data_source<&[u8], DhcpOption>,
+    chain!(
+        tag!([157u8]) ~
+        source: be_u8,
+        || { DataSource(source) }
+    )
+);
+named!(port_parameters<&[u8], DhcpOption>,
+    chain!(
+        tag!([159u8]) ~
+        params: map_opt!(take_rest, address_sharing::parse_port_parameters),
+        || { V4PortParams(params) }
+    )
+);
+named!(legacy_captive_portal<&[u8], DhcpOption>,
+    chain!(
+        tag!([160u8]) ~
+        data: take_rest,
+        || {
+            match any_uri(data) {
+                Some(uri) => LegacyCaptivePortal(uri),
+                None => Unknown { code: 160, data: data.to_vec() },
+            }
+        }
+    )
+);
+named!(mud<&[u8], DhcpOption>,
+    chain!(
+        tag!([161u8]) ~
+        url: map_opt!(take_rest, mud_url),
+        || { MudUrl(url) }
+    )
+);
+named!(encrypted_dns<&[u8], DhcpOption>,
+    chain!(
+        tag!([162u8]) ~
+        instances: map_opt!(take_rest, dnr::parse),
+        || { EncryptedDns(instances) }
+    )
+);
+domain_names!(sip_ua_config_service_domains, 141u8, SipUaConfigServiceDomains);
+named!(six_rd<&[u8], DhcpOption>,
+    chain!(
+        tag!([212u8]) ~
+        six_rd: map_opt!(take_rest, address_sharing::parse_six_rd),
+        || { SixRdConfig(six_rd) }
+    )
+);
+single_domain_name!(access_domain, 213u8, AccessDomain);
 
 // COLLECT
-named!(dhcp_extensions<&[u8], DhcpOption>, alt!(
-          requested_ip_address  // 50
-        | ip_address_lease_time
-        | option_overload
-        | message_type
-        | server_identifier
-        | param_request_list    // 55
-        | message
-/*         | max_message_size */
-/*         | renewal_time_value */
-/*         | rebinding_time_value */
-/*         | class_identifier      // 60 */
-/*         | client_identifier */
-    )
-);
-
-// Main parser
-named!(dhcp_option(&'a [u8]) -> DhcpOption, alt!(
-          vendor_extensions_rfc1497
-        | ip_layer_parameters_per_host
-        | ip_layer_parameters_per_interface
-        | link_layer_parameters_per_interface
-        | tcp_parameters
-        | application_and_service_parameters
-        | dhcp_extensions
+named!(later_extensions<&[u8], DhcpOption>, switch!(peek!(be_u8),
+          81u8 => call!(client_fqdn)
+        | 82u8 => call!(relay_agent_information)
+        | 88u8 => call!(bcmcs_controller_domain_names)
+        | 90u8 => call!(authentication)
+        | 91u8 => call!(client_last_transaction_time)
+        | 92u8 => call!(associated_ip)
+        | 93u8 => call!(client_system_architecture)
+        | 94u8 => call!(client_network_interface_identifier)
+        | 97u8 => call!(client_machine_identifier)
+        | 99u8 => call!(civic_address)
+        | 100u8 => call!(posix_time_zone)
+        | 101u8 => call!(tzdb_time_zone)
+        | 108u8 => call!(ipv6_only_preferred)
+        | 114u8 => call!(captive_portal)
+        | 119u8 => call!(domain_search)
+        | 121u8 => call!(classless_static_route)
+        | 123u8 => call!(geo_conf)
+        | 124u8 => call!(vendor_identifying_vendor_class)
+        | 125u8 => call!(vendor_identifying_vendor_specific)
+        | 137u8 => call!(lost_server)
+        | 141u8 => call!(sip_ua_config_service_domains)
+        | 144u8 => call!(geo_loc)
+        | 151u8 => call!(status_code)
+        | 152u8 => call!(base_time)
+        | 153u8 => call!(start_time_of_state)
+        | 154u8 => call!(query_start_time)
+        | 155u8 => call!(query_end_time)
+        | 156u8 => call!(dhcp_state)
+        | 157u8 => call!(data_source)
+        | 159u8 => call!(port_parameters)
+        | 160u8 => call!(legacy_captive_portal)
+        | 161u8 => call!(mud)
+        | 162u8 => call!(encrypted_dns)
+        | 212u8 => call!(six_rd)
+        | 213u8 => call!(access_domain)
+        | 249u8 => call!(ms_classless_static_route)
     )
 );
 
+// Main parser: tries each group in turn. When no group has a parser
+// for the code, nom's `Switch` error is passed on, and `decode` keeps
+// the option as `Unknown`.
+fn dhcp_option(input: &[u8]) -> IResult<&[u8], DhcpOption> {
+    let groups: [fn(&[u8]) -> IResult<&[u8], DhcpOption>; 9] = [
+        vendor_extensions_rfc1497,
+        ip_layer_parameters_per_host,
+        ip_layer_parameters_per_interface,
+        link_layer_parameters_per_interface,
+        tcp_parameters,
+        application_and_service_parameters,
+        dhcp_extensions,
+        more_application_and_service_parameters,
+        later_extensions,
+    ];
+    let mut result = IResult::Error(Err::Position(ErrorKind::Switch, input));
+    for group in groups.iter() {
+        result = group(input);
+        match result {
+            IResult::Error(Err::Position(ErrorKind::Switch, _)) => continue,
+            _ => break,
+        }
+    }
+    result
+}
+
 #[cfg(test)] mod tests {
-    use options::DhcpOption::{Router};
-    use super::{router};
+    use options::DhcpOption::*;
+    use options::{DhcpMessageTypes, RelayAgentSubOption, Route, VendorClass, VendorSpecificInformation};
+    use options::{ClientArchitecture, Uuid, DATA_SOURCE_REMOTE};
+    use options::{self, StatusCodeType, DhcpStateType, PosixTz, PortParameters};
+    use super::{router, parse, parse_at, parse_lenient_at, parse_areas};
+    use {Error};
     use nom::{IResult};
-    use std::net::{IpAddr, Ipv4Addr};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
     #[test]
     fn test_many_ip_addresses() {
         let ips = vec![3u8,
-                       8,
                        127, 0, 0, 1,
                        192, 168, 1, 1,
         ];
@@ -454,4 +994,423 @@ named!(dhcp_option(&'a [u8]) -> DhcpOption, alt!(
             e => panic!("Result was {:?}", e),
         }
     }
+
+    #[test]
+    fn test_parse_options() {
+        let bytes = vec![53u8, 1, 5,
+                         0, 0,
+                         51, 4, 0, 1, 81, 128,
+                         3, 4, 192, 168, 1, 1,
+                         12, 3, 102, 111, 111,
+                         255,
+                         0, 0, 0,
+        ];
+
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            MessageType(DhcpMessageTypes::Ack),
+            IpAddressLeaseTime(86400),
+            Router(vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))]),
+            HostName("foo".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_options_without_end() {
+        assert_eq!(parse(&[]).unwrap(), vec![]);
+        assert_eq!(parse(&[53u8, 1, 1]).unwrap(), vec![MessageType(DhcpMessageTypes::Discover)]);
+    }
+
+    #[test]
+    fn test_parse_options_truncated() {
+        assert_eq!(parse(&[3u8, 8, 192, 168, 1, 1, 255]),
+                   Err(Error::OptionLengthMismatch { offset: 0, code: 3 }));
+        assert_eq!(parse(&[53u8]), Err(Error::OptionLengthMismatch { offset: 0, code: 53 }));
+        assert_eq!(parse(&[53u8, 1, 1, 51, 2, 0, 1, 255]),
+                   Err(Error::OptionLengthMismatch { offset: 3, code: 51 }));
+    }
+
+    #[test]
+    fn test_parse_options_errors() {
+        assert_eq!(parse_at(&[53u8, 1, 1, 52, 1, 100, 255], 240),
+                   Err(Error::InvalidOptionValue { offset: 243, code: 52 }));
+        assert_eq!(parse(&[0u8, 15, 2, 0xff, 0xfe, 255]),
+                   Err(Error::InvalidUtf8 { offset: 1, code: 15 }));
+        assert_eq!(parse(&[53u8, 1, 1, 255, 0, 0, 53]),
+                   Err(Error::LeftoverInput { offset: 6 }));
+        // lenient parsing reports it, but keeps the options
+        assert_eq!(parse_lenient_at(&[53u8, 1, 1, 255, 0, 0, 53], 0),
+                   (vec![MessageType(DhcpMessageTypes::Discover)], vec![Error::LeftoverInput { offset: 6 }]));
+    }
+
+    #[test]
+    fn test_parse_unknown_options() {
+        let bytes = vec![53u8, 1, 3,
+                         224, 3, 1, 2, 3,
+                         116, 0,
+                         250, 0,
+                         255,
+        ];
+
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            MessageType(DhcpMessageTypes::Request),
+            Unknown { code: 224, data: vec![1, 2, 3] },
+            Unknown { code: 116, data: vec![] },
+            Unknown { code: 250, data: vec![] },
+        ]);
+        assert!(parse(&[224u8, 4, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_parse_domain_search() {
+        let bytes = vec![119u8, 15, 3, b'e', b'n', b'g', 5, b'a', b'p', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0,
+                         53, 1, 1,
+                         119, 12, 9, b'm', b'a', b'r', b'k', b'e', b't', b'i', b'n', b'g', 0xc0, 4,
+                         137, 5, 3, b'l', b'o', b's', 0,
+                         255,
+        ];
+
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            DomainSearch(vec!["eng.apple.com".to_owned(), "marketing.apple.com".to_owned()]),
+            MessageType(DhcpMessageTypes::Discover),
+            LostServer("los".to_owned()),
+        ]);
+        assert_eq!(parse(&[119u8, 2, 0xc0, 0]), Err(Error::InvalidOptionValue { offset: 0, code: 119 }));
+        assert_eq!(parse(&[213u8, 2, 0, 0]), Err(Error::InvalidOptionValue { offset: 0, code: 213 }));
+    }
+
+    #[test]
+    fn test_parse_message_types() {
+        assert_eq!(parse(&[53u8, 1, 8]).unwrap(), vec![MessageType(DhcpMessageTypes::Inform)]);
+        assert_eq!(parse(&[53u8, 1, 9]).unwrap(), vec![MessageType(DhcpMessageTypes::ForceRenew)]);
+        assert_eq!(parse(&[53u8, 1, 18]).unwrap(), vec![MessageType(DhcpMessageTypes::Tls)]);
+        assert_eq!(parse(&[53u8, 1, 100]).unwrap(), vec![MessageType(DhcpMessageTypes::Unknown(100))]);
+        assert_eq!(parse(&[53u8, 2, 8, 0]), Err(Error::OptionLengthMismatch { offset: 0, code: 53 }));
+        for b in 0..255u8 {
+            assert_eq!(DhcpMessageTypes::from_byte(b).to_byte(), b);
+        }
+    }
+
+    #[test]
+    fn test_parse_leasequery_options() {
+        let bytes = vec![91u8, 4, 0, 0, 0, 60,
+                         92, 8, 10, 0, 0, 1, 10, 0, 0, 2,
+                         151, 3, 0, b'o', b'k',
+                         152, 4, 0, 0, 1, 0,
+                         153, 4, 0, 0, 0, 1,
+                         154, 4, 0, 0, 0, 2,
+                         155, 4, 0, 0, 0, 3,
+                         156, 1, 2,
+                         157, 1, 1,
+                         255];
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            ClientLastTransactionTime(60),
+            AssociatedIp(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]),
+            StatusCode { code: StatusCodeType::Success, message: "ok".to_owned() },
+            BaseTime(256),
+            StartTimeOfState(1),
+            QueryStartTime(2),
+            QueryEndTime(3),
+            DhcpState(DhcpStateType::Active),
+            DataSource(DATA_SOURCE_REMOTE),
+        ]);
+        assert_eq!(parse(&[151u8, 1, 200]).unwrap(), vec![StatusCode { code: StatusCodeType::Unknown(200), message: "".to_owned() }]);
+        assert_eq!(parse(&[151u8, 0]), Err(Error::OptionLengthMismatch { offset: 0, code: 151 }));
+        assert_eq!(parse(&[156u8, 1, 9]).unwrap(), vec![DhcpState(DhcpStateType::Unknown(9))]);
+        assert_eq!(parse(&[157u8, 2, 1, 0]), Err(Error::OptionLengthMismatch { offset: 0, code: 157 }));
+    }
+
+    #[test]
+    fn test_parse_uri_options() {
+        let mut bytes = vec![108u8, 4, 0, 0, 0, 60, 114, 20];
+        bytes.extend(b"https://portal.test/".iter().cloned());
+        bytes.extend(vec![160u8, 36].into_iter());
+        bytes.extend(b"urn:ietf:params:capport:unrestricted".iter().cloned());
+        bytes.extend(vec![161u8, 21].into_iter());
+        bytes.extend(b"https://mud.test/x.js".iter().cloned());
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            Ipv6OnlyPreferred(60),
+            CaptivePortal("https://portal.test/".to_owned()),
+            LegacyCaptivePortal("urn:ietf:params:capport:unrestricted".to_owned()),
+            MudUrl("https://mud.test/x.js".to_owned()),
+        ]);
+
+        let mut bytes = vec![114u8, 19];
+        bytes.extend(b"http://portal.test/".iter().cloned());
+        assert_eq!(parse(&bytes), Err(Error::InvalidOptionValue { offset: 0, code: 114 }));
+        let mut bytes = vec![161u8, 11];
+        bytes.extend(b"https://a b".iter().cloned());
+        assert_eq!(parse(&bytes), Err(Error::InvalidOptionValue { offset: 0, code: 161 }));
+
+        // Polycom phones send provisioning URLs in 160
+        let mut bytes = vec![160u8, 23];
+        bytes.extend(b"ftp://prov.test/polycom".iter().cloned());
+        assert_eq!(parse(&bytes).unwrap(), vec![LegacyCaptivePortal("ftp://prov.test/polycom".to_owned())]);
+        let mut bytes = vec![160u8, 9];
+        bytes.extend(b"prov.test".iter().cloned());
+        assert_eq!(parse(&bytes).unwrap(), vec![Unknown { code: 160, data: b"prov.test".to_vec() }]);
+    }
+
+    #[test]
+    fn test_parse_time_zones() {
+        let mut bytes = vec![100u8, 26];
+        bytes.extend(b"EST5EDT,M3.2.0/2,M11.1.0/2".iter().cloned());
+        bytes.extend(vec![101u8, 13].into_iter());
+        bytes.extend(b"Europe/Zurich".iter().cloned());
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            PosixTimeZone(PosixTz::parse("EST5EDT,M3.2.0/2,M11.1.0/2").unwrap()),
+            TzdbTimeZone("Europe/Zurich".to_owned()),
+        ]);
+
+        let mut bytes = vec![100u8, 8];
+        bytes.extend(b"EST5EDT,".iter().cloned());
+        assert_eq!(parse(&bytes[..]), Err(Error::InvalidOptionValue { offset: 0, code: 100 }));
+    }
+
+    #[test]
+    fn test_parse_geolocation() {
+        let bytes = vec![99u8, 7, 2, b'U', b'S', 1, 2, b'D', b'C',
+                         144, 16, 0x48, 0x4d, 0x00, 0x00, 0x00, 0x4b, 0x65, 0x80, 0x00, 0x00, 0x15, 0x00, 0x00, 0x0f,
+                         0xc0, 0x41];
+        let options = parse(&bytes).unwrap();
+        assert_eq!(options[0], CivicAddress(options::CivicAddress {
+            what: 2,
+            country_code: "US".to_owned(),
+            elements: vec![(1, "DC".to_owned())],
+        }));
+        match options[1] {
+            GeoLoc(ref c) => assert_eq!((c.latitude, c.longitude, c.altitude), (38.5, -77.25, 15.75)),
+            ref o => panic!("{:?}", o),
+        }
+
+        let mut bytes = vec![123u8, 16];
+        bytes.extend(vec![0u8; 16].into_iter());
+        assert_eq!(parse(&bytes), Err(Error::InvalidOptionValue { offset: 0, code: 123 }));
+    }
+
+    #[test]
+    fn test_parse_address_sharing() {
+        let bytes = vec![159u8, 4, 6, 8, 0x34, 0,
+                         212, 22, 8, 32, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                         192, 0, 2, 1];
+        let options = parse(&bytes).unwrap();
+        assert_eq!(options[0], V4PortParams(PortParameters { offset: 6, psid_len: 8, psid: 0x34 }));
+        match options[1] {
+            SixRdConfig(ref six_rd) => {
+                assert_eq!(six_rd.border_relays, vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]);
+                assert_eq!(six_rd.delegated_prefix(&IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+                           Some((Ipv6Addr::new(0x2001, 0xdb8, 0, 0x100, 0, 0, 0, 0), 56)));
+            },
+            ref o => panic!("{:?}", o),
+        }
+
+        assert_eq!(parse(&[159u8, 4, 6, 8, 0x34, 1]), Err(Error::InvalidOptionValue { offset: 0, code: 159 }));
+    }
+
+    #[test]
+    fn test_parse_client_fqdn() {
+        let bytes = vec![81u8, 8, 0x05, 0, 0, 4, b'h', b'o', b's', b't', 255];
+        let options = parse(&bytes).unwrap();
+        assert_eq!(options.len(), 1);
+        match options[0] {
+            ClientFqdn(ref fqdn) => {
+                assert!(fqdn.flags.server_update && fqdn.flags.encoded);
+                assert_eq!((&fqdn.name[..], fqdn.partial), ("host", true));
+            },
+            ref o => panic!("unexpected options {:?}", o),
+        }
+        assert_eq!(parse(&[81u8, 2, 0, 0]), Err(Error::InvalidOptionValue { offset: 0, code: 81 }));
+    }
+
+    #[test]
+    fn test_parse_vendor_identifying() {
+        let bytes = vec![124u8, 9, 0, 0, 17, 139, 4, 3, b'1', b'.', b'0',
+                         125, 15, 0, 0, 13, 233, 10, 11, 8, b'h', b't', b't', b'p', b':', b'/', b'/', b'a',
+                         255];
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            VendorIdentifyingVendorClass(vec![VendorClass { enterprise_number: 4491, data: vec![b"1.0".to_vec()] }]),
+            VendorIdentifyingVendorSpecific(vec![VendorSpecificInformation {
+                enterprise_number: 3561,
+                sub_options: vec![(11, b"http://a".to_vec())],
+            }]),
+        ]);
+        assert_eq!(parse(&[125u8, 6, 0, 0, 13, 233, 2, 11]), Err(Error::InvalidOptionValue { offset: 0, code: 125 }));
+    }
+
+    #[test]
+    fn test_parse_pxe_client_options() {
+        let mut bytes = vec![93u8, 2, 0, 7,
+                             94, 3, 1, 2, 1,
+                             97, 17, 0];
+        bytes.extend(vec![0xaau8; 16].into_iter());
+        bytes.push(255);
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            ClientSystemArchitecture(vec![ClientArchitecture::X64Uefi]),
+            ClientNetworkInterfaceIdentifier { interface_type: 1, major: 2, minor: 1 },
+            ClientMachineIdentifier(Uuid([0xaa; 16])),
+        ]);
+        assert_eq!(parse(&[94u8, 2, 1, 2]), Err(Error::OptionLengthMismatch { offset: 0, code: 94 }));
+        assert_eq!(parse(&[97u8, 1, 0]), Err(Error::InvalidOptionValue { offset: 0, code: 97 }));
+    }
+
+    #[test]
+    fn test_parse_split_domain_search() {
+        // the second instance points back into the first, so they only
+        // make sense once concatenated
+        let bytes = vec![119u8, 5, 1, b'a', 1, b'b', 0,
+                         3, 4, 10, 0, 0, 1,
+                         119, 4, 1, b'c', 0xc0, 2,
+                         255];
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            DomainSearch(vec!["a.b".to_owned(), "c.b".to_owned()]),
+            Router(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]),
+        ]);
+
+        // long enough that the joined data doesn't fit in one option
+        let mut bytes = vec![];
+        let label = vec![b'x'; 63];
+        for _ in 0..3 {
+            bytes.extend(vec![119u8, 65, 63].into_iter());
+            bytes.extend(label.iter().cloned());
+            bytes.push(0);
+        }
+        let name = String::from_utf8(label).unwrap();
+        assert_eq!(parse(&bytes).unwrap(), vec![DomainSearch(vec![name.clone(), name.clone(), name])]);
+    }
+
+    #[test]
+    fn test_parse_concatenated() {
+        // RFC 3396: every instance of an option is part of the same one
+        let bytes = vec![3u8, 4, 10, 0, 0, 1, 53, 1, 1, 3, 4, 10, 0, 0, 2, 255];
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            Router(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]),
+            MessageType(DhcpMessageTypes::Discover),
+        ]);
+        assert_eq!(parse(&[53u8, 1, 1, 53, 1, 1, 255]), Err(Error::OptionLengthMismatch { offset: 0, code: 53 }));
+
+        // split across areas, and with the joined data too long to fit
+        // into a single option
+        let mut options = vec![12u8, 200];
+        options.extend(vec![b'a'; 200].into_iter());
+        let mut file = vec![12u8, 100];
+        file.extend(vec![b'b'; 100].into_iter());
+        let (parsed, errors) = parse_areas(&[(&options, 240), (&file, 108)]);
+        assert_eq!(errors, vec![]);
+        let mut name = String::from_utf8(vec![b'a'; 200]).unwrap();
+        name.push_str(&String::from_utf8(vec![b'b'; 100]).unwrap());
+        assert_eq!(parsed, vec![HostName(name)]);
+
+        file[2] = 0xff;
+        let (_, errors) = parse_areas(&[(&options, 240), (&file, 108)]);
+        assert_eq!(errors, vec![Error::InvalidUtf8 { offset: 240, code: 12 }]);
+    }
+
+    #[test]
+    fn test_parse_options_lenient() {
+        let bytes = vec![53u8, 1, 1,
+                         51, 2, 0, 1,
+                         12, 2, 0xc3, 0x28,
+                         54, 4, 10, 0, 0, 1,
+                         255,
+                         1,
+        ];
+
+        let (options, errors) = parse_lenient_at(&bytes, 240);
+        assert_eq!(options, vec![
+            MessageType(DhcpMessageTypes::Discover),
+            ServerIdentifier(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+        ]);
+        assert_eq!(errors, vec![
+            Error::OptionLengthMismatch { offset: 243, code: 51 },
+            Error::InvalidUtf8 { offset: 247, code: 12 },
+            Error::LeftoverInput { offset: 258 },
+        ]);
+        assert_eq!(parse_at(&bytes, 240), Err(Error::OptionLengthMismatch { offset: 243, code: 51 }));
+
+        let (options, errors) = parse_lenient_at(&[53u8, 1, 1, 3, 8, 10, 0], 0);
+        assert_eq!(options, vec![MessageType(DhcpMessageTypes::Discover)]);
+        assert_eq!(errors, vec![Error::OptionLengthMismatch { offset: 3, code: 3 }]);
+    }
+
+    #[test]
+    fn test_parse_rfc2132_extensions() {
+        let bytes = vec![21u8, 8, 10, 0, 0, 0, 255, 0, 0, 0,
+                         57, 2, 5, 220,
+                         58, 4, 0, 0, 7, 8,
+                         59, 4, 0, 0, 12, 78,
+                         60, 4, 77, 83, 70, 84,
+                         61, 7, 1, 0, 17, 34, 51, 68, 85,
+                         63, 5, 5, 1, 1, 6, 0,
+                         64, 3, 110, 105, 115,
+                         66, 4, 116, 102, 116, 112,
+                         67, 4, 98, 111, 111, 116,
+                         68, 0,
+                         69, 4, 10, 0, 0, 25,
+                         76, 4, 10, 0, 0, 76,
+                         255,
+        ];
+
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            PolicyFilter(vec![(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)),
+                               IpAddr::V4(Ipv4Addr::new(255, 0, 0, 0)))]),
+            MaxMessageSize(1500),
+            RenewalTimeValue(1800),
+            RebindingTimeValue(3150),
+            ClassIdentifier(b"MSFT".to_vec()),
+            ClientIdentifier { id_type: 1, id: vec![0, 17, 34, 51, 68, 85] },
+            NetWareIpInformation(vec![(5, vec![1]), (6, vec![])]),
+            NisPlusDomain("nis".to_owned()),
+            TftpServerName("tftp".to_owned()),
+            BootfileName("boot".to_owned()),
+            MobileIpHomeAgent(vec![]),
+            SmtpServer(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 25))]),
+            StdaServer(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 76))]),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_bad_rfc2132_extensions() {
+        assert_eq!(parse(&[61u8, 1, 1]), Err(Error::InvalidOptionValue { offset: 0, code: 61 }));
+        assert_eq!(parse(&[63u8, 3, 5, 2, 1]), Err(Error::InvalidOptionValue { offset: 0, code: 63 }));
+        assert_eq!(parse(&[58u8, 2, 0, 0]), Err(Error::OptionLengthMismatch { offset: 0, code: 58 }));
+    }
+
+    #[test]
+    fn test_parse_relay_agent_information() {
+        let bytes = vec![53u8, 1, 1,
+                         82, 12, 1, 4, 0, 1, 0, 10, 2, 4, 10, 0, 0, 1,
+                         255,
+        ];
+
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            MessageType(DhcpMessageTypes::Discover),
+            RelayAgentInformation(vec![RelayAgentSubOption::CircuitId(vec![0, 1, 0, 10]),
+                                       RelayAgentSubOption::RemoteId(vec![10, 0, 0, 1])]),
+        ]);
+        assert_eq!(parse(&[82u8, 3, 1, 4, 0]), Err(Error::InvalidOptionValue { offset: 0, code: 82 }));
+    }
+
+    #[test]
+    fn test_parse_classless_static_routes() {
+        let bytes = vec![121u8, 5, 0, 10, 0, 0, 1,
+                         249, 7, 16, 192, 168, 10, 0, 0, 2,
+                         255,
+        ];
+        let default = Route {
+            destination: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+            prefix_len: 0,
+            gateway: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        };
+        let lan = Route {
+            destination: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0)),
+            prefix_len: 16,
+            gateway: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+        };
+
+        assert_eq!(parse(&bytes).unwrap(), vec![
+            ClasslessStaticRoute(vec![default]),
+            MsClasslessStaticRoute(vec![lan]),
+        ]);
+        assert_eq!(parse(&[121u8, 5, 33, 10, 0, 0, 1]),
+                   Err(Error::InvalidOptionValue { offset: 0, code: 121 }));
+    }
 }
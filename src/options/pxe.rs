@@ -0,0 +1,479 @@
+//! PXE client options 93, 94 and 97 (RFC 4578), and the PXE sub-options
+//! that PXE servers send inside option 43 (PXE specification 2.1)
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::str;
+
+use {Result, Error};
+use options::encode::{sub_options as encode_sub_options};
+use util::{u16_bytes, u16_from_bytes, ip};
+
+/// Client system architecture types, as listed in the IANA "Processor
+/// Architecture Types" registry
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ClientArchitecture {
+    X86Bios,
+    NecPc98,
+    Itanium,
+    DecAlpha,
+    ArcX86,
+    IntelLeanClient,
+    X86Uefi,
+    /// 7: called "EFI BC" by RFC 4578, and sent by x64 UEFI firmware
+    X64Uefi,
+    EfiXscale,
+    /// 9: EFI Byte Code
+    Ebc,
+    Arm32Uefi,
+    Arm64Uefi,
+    PowerPcOpenFirmware,
+    PowerPcEpapr,
+    PowerOpalV3,
+    X86UefiHttp,
+    X64UefiHttp,
+    EbcHttp,
+    Arm32UefiHttp,
+    Arm64UefiHttp,
+    X86BiosHttp,
+    Arm32Uboot,
+    Arm64Uboot,
+    Arm32UbootHttp,
+    Arm64UbootHttp,
+    RiscV32Uefi,
+    RiscV32UefiHttp,
+    RiscV64Uefi,
+    RiscV64UefiHttp,
+    RiscV128Uefi,
+    RiscV128UefiHttp,
+    S390Basic,
+    S390Extended,
+    Mips32Uefi,
+    Mips64Uefi,
+    Sunway32Uefi,
+    Sunway64Uefi,
+    LoongArch32Uefi,
+    LoongArch32UefiHttp,
+    LoongArch64Uefi,
+    LoongArch64UefiHttp,
+    ArmRpiboot,
+    Unknown(u16),
+}
+
+impl ClientArchitecture {
+    pub fn from_u16(value: u16) -> ClientArchitecture {
+        use self::ClientArchitecture::*;
+        match value {
+            0 => X86Bios,
+            1 => NecPc98,
+            2 => Itanium,
+            3 => DecAlpha,
+            4 => ArcX86,
+            5 => IntelLeanClient,
+            6 => X86Uefi,
+            7 => X64Uefi,
+            8 => EfiXscale,
+            9 => Ebc,
+            10 => Arm32Uefi,
+            11 => Arm64Uefi,
+            12 => PowerPcOpenFirmware,
+            13 => PowerPcEpapr,
+            14 => PowerOpalV3,
+            15 => X86UefiHttp,
+            16 => X64UefiHttp,
+            17 => EbcHttp,
+            18 => Arm32UefiHttp,
+            19 => Arm64UefiHttp,
+            20 => X86BiosHttp,
+            21 => Arm32Uboot,
+            22 => Arm64Uboot,
+            23 => Arm32UbootHttp,
+            24 => Arm64UbootHttp,
+            25 => RiscV32Uefi,
+            26 => RiscV32UefiHttp,
+            27 => RiscV64Uefi,
+            28 => RiscV64UefiHttp,
+            29 => RiscV128Uefi,
+            30 => RiscV128UefiHttp,
+            31 => S390Basic,
+            32 => S390Extended,
+            33 => Mips32Uefi,
+            34 => Mips64Uefi,
+            35 => Sunway32Uefi,
+            36 => Sunway64Uefi,
+            37 => LoongArch32Uefi,
+            38 => LoongArch32UefiHttp,
+            39 => LoongArch64Uefi,
+            40 => LoongArch64UefiHttp,
+            41 => ArmRpiboot,
+            v => Unknown(v),
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        use self::ClientArchitecture::*;
+        match *self {
+            X86Bios => 0,
+            NecPc98 => 1,
+            Itanium => 2,
+            DecAlpha => 3,
+            ArcX86 => 4,
+            IntelLeanClient => 5,
+            X86Uefi => 6,
+            X64Uefi => 7,
+            EfiXscale => 8,
+            Ebc => 9,
+            Arm32Uefi => 10,
+            Arm64Uefi => 11,
+            PowerPcOpenFirmware => 12,
+            PowerPcEpapr => 13,
+            PowerOpalV3 => 14,
+            X86UefiHttp => 15,
+            X64UefiHttp => 16,
+            EbcHttp => 17,
+            Arm32UefiHttp => 18,
+            Arm64UefiHttp => 19,
+            X86BiosHttp => 20,
+            Arm32Uboot => 21,
+            Arm64Uboot => 22,
+            Arm32UbootHttp => 23,
+            Arm64UbootHttp => 24,
+            RiscV32Uefi => 25,
+            RiscV32UefiHttp => 26,
+            RiscV64Uefi => 27,
+            RiscV64UefiHttp => 28,
+            RiscV128Uefi => 29,
+            RiscV128UefiHttp => 30,
+            S390Basic => 31,
+            S390Extended => 32,
+            Mips32Uefi => 33,
+            Mips64Uefi => 34,
+            Sunway32Uefi => 35,
+            Sunway64Uefi => 36,
+            LoongArch32Uefi => 37,
+            LoongArch32UefiHttp => 38,
+            LoongArch64Uefi => 39,
+            LoongArch64UefiHttp => 40,
+            ArmRpiboot => 41,
+            Unknown(v) => v,
+        }
+    }
+
+    /// Whether the client boots over HTTP rather than TFTP (the UEFI
+    /// HTTP boot types), so the boot file should be given as a URL
+    pub fn is_http(&self) -> bool {
+        use self::ClientArchitecture::*;
+        match *self {
+            X86UefiHttp | X64UefiHttp | EbcHttp | Arm32UefiHttp | Arm64UefiHttp | X86BiosHttp |
+            Arm32UbootHttp | Arm64UbootHttp | RiscV32UefiHttp | RiscV64UefiHttp | RiscV128UefiHttp |
+            LoongArch32UefiHttp | LoongArch64UefiHttp => true,
+            _ => false,
+        }
+    }
+}
+
+/// A client machine UUID/GUID, from option 97
+///
+/// The bytes are kept in the order they were sent. Many PXE clients
+/// send the SMBIOS UUID, whose first three fields are little-endian, so
+/// the `Display` form may not match what the machine's firmware shows.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Uuid(pub [u8; 16]);
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if i == 4 || i == 6 || i == 8 || i == 10 {
+                try!(write!(f, "-"));
+            }
+            try!(write!(f, "{:02x}", b));
+        }
+        Ok(())
+    }
+}
+
+/// Interface type 1 in option 94: the Universal Network Device Interface
+pub const INTERFACE_UNDI: u8 = 1;
+
+/// Bits of the `DiscoveryControl` sub-option
+pub const DISCOVERY_DISABLE_BROADCAST: u8 = 0x01;
+pub const DISCOVERY_DISABLE_MULTICAST: u8 = 0x02;
+pub const DISCOVERY_ONLY_BOOT_SERVERS: u8 = 0x04;
+pub const DISCOVERY_DOWNLOAD_BOOT_FILE: u8 = 0x08;
+
+/// A boot server type and the servers that provide it
+#[derive(Debug, PartialEq, Clone)]
+pub struct BootServer {
+    pub server_type: u16,
+    pub addresses: Vec<IpAddr>,
+}
+
+/// An entry in the boot menu
+#[derive(Debug, PartialEq, Clone)]
+pub struct BootMenuItem {
+    pub server_type: u16,
+    pub description: String,
+}
+
+/// A PXE sub-option, as sent inside option 43
+#[derive(Debug, PartialEq, Clone)]
+pub enum PxeSubOption {
+    /// 1: multicast address for MTFTP
+    MtftpIp(IpAddr),
+    /// 2: MTFTP client port
+    MtftpClientPort(u16),
+    /// 3: MTFTP server port
+    MtftpServerPort(u16),
+    /// 4: seconds to wait before starting an MTFTP transfer
+    MtftpTimeout(u8),
+    /// 5: seconds to wait before restarting an MTFTP transfer
+    MtftpDelay(u8),
+    /// 6: how to discover boot servers, see the `DISCOVERY_*` bits
+    DiscoveryControl(u8),
+    /// 7: multicast address for boot server discovery
+    DiscoveryMulticastAddress(IpAddr),
+    /// 8: boot servers, by type
+    BootServers(Vec<BootServer>),
+    /// 9: the boot menu
+    BootMenu(Vec<BootMenuItem>),
+    /// 10: how long to show the menu prompt for, and the prompt itself
+    MenuPrompt { timeout: u8, prompt: String },
+    /// 71: the boot server type and layer being requested or offered
+    BootItem { server_type: u16, layer: u16 },
+    /// Any sub-option we don't know how to decode
+    Unknown { code: u8, data: Vec<u8> },
+}
+
+/// Decodes an option 93 payload, a list of 16 bit architecture types
+pub fn parse_architectures(bytes: &[u8]) -> Option<Vec<ClientArchitecture>> {
+    if bytes.is_empty() || bytes.len() % 2 != 0 {
+        return None;
+    }
+    Some(bytes.chunks(2).map(|c| ClientArchitecture::from_u16(u16_from_bytes(c))).collect())
+}
+
+/// Decodes an option 97 payload, which only has a type 0 UUID defined
+pub fn parse_uuid(bytes: &[u8]) -> Option<Uuid> {
+    if bytes.len() != 17 || bytes[0] != 0 {
+        return None;
+    }
+    let mut uuid = [0u8; 16];
+    uuid.copy_from_slice(&bytes[1..]);
+    Some(Uuid(uuid))
+}
+
+/// Splits the contents of option 43 into PXE sub-options. Only do this
+/// when the client identified itself as a PXE client in option 60.
+///
+/// The PXE specification ends the sub-options with an End (255), which
+/// isn't included in the result, and anything after it is ignored. Pad
+/// (0) is skipped. Returns `None` if any sub-option is malformed.
+pub fn parse(bytes: &[u8]) -> Option<Vec<PxeSubOption>> {
+    let mut subs = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        match rest[0] {
+            0 => {
+                rest = &rest[1..];
+                continue;
+            },
+            255 => break,
+            _ => {},
+        }
+        if rest.len() < 2 || rest.len() < 2 + rest[1] as usize {
+            return None;
+        }
+        let end = 2 + rest[1] as usize;
+        subs.push(try_opt!(parse_sub_option(rest[0], rest[2..end].to_vec())));
+        rest = &rest[end..];
+    }
+    Some(subs)
+}
+
+fn parse_sub_option(code: u8, data: Vec<u8>) -> Option<PxeSubOption> {
+    use self::PxeSubOption::*;
+    Some(match (code, data.len()) {
+        (1, 4) => MtftpIp(ipv4(&data)),
+        (2, 2) => MtftpClientPort(u16_from_bytes(&data)),
+        (3, 2) => MtftpServerPort(u16_from_bytes(&data)),
+        (4, 1) => MtftpTimeout(data[0]),
+        (5, 1) => MtftpDelay(data[0]),
+        (6, 1) => DiscoveryControl(data[0]),
+        (7, 4) => DiscoveryMulticastAddress(ipv4(&data)),
+        (8, _) => BootServers(try_opt!(boot_servers(&data))),
+        (9, _) => BootMenu(try_opt!(boot_menu(&data))),
+        (10, n) if n >= 1 => MenuPrompt {
+            timeout: data[0],
+            prompt: try_opt!(str::from_utf8(&data[1..]).ok()).to_owned(),
+        },
+        (71, 4) => BootItem { server_type: u16_from_bytes(&data[..2]), layer: u16_from_bytes(&data[2..]) },
+        (1...10, _) | (71, _) => return None,
+        _ => Unknown { code: code, data: data },
+    })
+}
+
+fn ipv4(bytes: &[u8]) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+}
+
+/// `[type(2), count, addresses...]` entries
+fn boot_servers(bytes: &[u8]) -> Option<Vec<BootServer>> {
+    let mut servers = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        if rest.len() < 3 || rest.len() < 3 + 4 * rest[2] as usize {
+            return None;
+        }
+        let end = 3 + 4 * rest[2] as usize;
+        servers.push(BootServer {
+            server_type: u16_from_bytes(rest),
+            addresses: rest[3..end].chunks(4).map(ipv4).collect(),
+        });
+        rest = &rest[end..];
+    }
+    Some(servers)
+}
+
+/// `[type(2), length, description...]` entries
+fn boot_menu(bytes: &[u8]) -> Option<Vec<BootMenuItem>> {
+    let mut items = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        if rest.len() < 3 || rest.len() < 3 + rest[2] as usize {
+            return None;
+        }
+        let end = 3 + rest[2] as usize;
+        items.push(BootMenuItem {
+            server_type: u16_from_bytes(rest),
+            description: try_opt!(str::from_utf8(&rest[3..end]).ok()).to_owned(),
+        });
+        rest = &rest[end..];
+    }
+    Some(items)
+}
+
+/// Encodes PXE sub-options into the contents of option 43, ending them
+/// with an End as the PXE specification asks
+pub fn encode(subs: &[PxeSubOption]) -> Result<Vec<u8>> {
+    use self::PxeSubOption::*;
+    let mut bytes = vec![];
+    for sub in subs {
+        let (code, data) = match *sub {
+            MtftpIp(ref a) => (1u8, try!(ip(a))),
+            MtftpClientPort(p) => (2u8, u16_bytes(p)),
+            MtftpServerPort(p) => (3u8, u16_bytes(p)),
+            MtftpTimeout(t) => (4u8, vec![t]),
+            MtftpDelay(t) => (5u8, vec![t]),
+            DiscoveryControl(c) => (6u8, vec![c]),
+            DiscoveryMulticastAddress(ref a) => (7u8, try!(ip(a))),
+            BootServers(ref servers) => {
+                let mut data = vec![];
+                for server in servers {
+                    if server.addresses.len() > 255 {
+                        return Err(Error::EncodeError(format!("Boot server type {} has too many addresses",
+                                                             server.server_type)));
+                    }
+                    data.extend(u16_bytes(server.server_type).into_iter());
+                    data.push(server.addresses.len() as u8);
+                    for a in &server.addresses {
+                        data.extend(try!(ip(a)).into_iter());
+                    }
+                }
+                (8u8, data)
+            },
+            BootMenu(ref items) => {
+                let mut data = vec![];
+                for item in items {
+                    if item.description.len() > 255 {
+                        return Err(Error::EncodeError(format!("Boot menu description for type {} is too long",
+                                                             item.server_type)));
+                    }
+                    data.extend(u16_bytes(item.server_type).into_iter());
+                    data.push(item.description.len() as u8);
+                    data.extend(item.description.as_bytes().iter().cloned());
+                }
+                (9u8, data)
+            },
+            MenuPrompt { timeout, ref prompt } => {
+                let mut data = vec![timeout];
+                data.extend(prompt.as_bytes().iter().cloned());
+                (10u8, data)
+            },
+            BootItem { server_type, layer } => {
+                let mut data = u16_bytes(server_type);
+                data.extend(u16_bytes(layer).into_iter());
+                (71u8, data)
+            },
+            Unknown { code, ref data } => (code, data.clone()),
+        };
+        bytes.extend(try!(encode_sub_options(43u8, &[(code, data)])).into_iter());
+    }
+    bytes.push(255u8);
+    Ok(bytes)
+}
+
+#[cfg(test)] mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use super::{parse, encode, parse_architectures, parse_uuid, ClientArchitecture, Uuid, BootServer, BootMenuItem};
+    use super::PxeSubOption::*;
+
+    #[test]
+    fn test_architectures() {
+        assert_eq!(parse_architectures(&[0u8, 7, 0, 16, 1, 0]).unwrap(),
+                   vec![ClientArchitecture::X64Uefi, ClientArchitecture::X64UefiHttp, ClientArchitecture::Unknown(256)]);
+        assert!(ClientArchitecture::X64UefiHttp.is_http());
+        assert!(!ClientArchitecture::Arm64Uefi.is_http());
+        assert_eq!(ClientArchitecture::from_u16(11), ClientArchitecture::Arm64Uefi);
+        for v in 0..50 {
+            assert_eq!(ClientArchitecture::from_u16(v).to_u16(), v);
+        }
+        assert_eq!(parse_architectures(&[]), None);
+        assert_eq!(parse_architectures(&[0u8, 7, 0]), None);
+    }
+
+    #[test]
+    fn test_uuid() {
+        let mut bytes = vec![0u8];
+        bytes.extend((0u8..16).into_iter());
+        let uuid = parse_uuid(&bytes).unwrap();
+        assert_eq!(uuid, Uuid([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]));
+        assert_eq!(uuid.to_string(), "00010203-0405-0607-0809-0a0b0c0d0e0f");
+        bytes[0] = 1;
+        assert_eq!(parse_uuid(&bytes), None);
+        assert_eq!(parse_uuid(&bytes[1..]), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = vec![6u8, 1, 11,
+                         8, 7, 128, 0, 1, 192, 168, 1, 1,
+                         9, 8, 128, 0, 5, b'L', b'i', b'n', b'u', b'x',
+                         10, 5, 3, b'B', b'o', b'o', b't',
+                         71, 4, 128, 0, 0, 0,
+                         255];
+        let subs = parse(&bytes).unwrap();
+        assert_eq!(subs, vec![
+            DiscoveryControl(super::DISCOVERY_DISABLE_BROADCAST | super::DISCOVERY_DISABLE_MULTICAST |
+                             super::DISCOVERY_DOWNLOAD_BOOT_FILE),
+            BootServers(vec![BootServer {
+                server_type: 0x8000,
+                addresses: vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))],
+            }]),
+            BootMenu(vec![BootMenuItem { server_type: 0x8000, description: "Linux".to_owned() }]),
+            MenuPrompt { timeout: 3, prompt: "Boot".to_owned() },
+            BootItem { server_type: 0x8000, layer: 0 },
+        ]);
+        assert_eq!(encode(&subs).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(parse(&[6u8, 2, 0, 0, 255]), None);
+        assert_eq!(parse(&[8u8, 3, 0, 1, 1, 255]), None);
+        assert_eq!(parse(&[71u8, 2, 0, 1, 255]), None);
+        // a 255 inside a sub-option isn't the end
+        assert_eq!(parse(&[4u8, 1, 255]).unwrap(), vec![MtftpTimeout(255)]);
+        assert_eq!(parse(&[4u8, 1, 255, 0, 6, 1, 8, 255, 5, 1]).unwrap(),
+                   vec![MtftpTimeout(255), DiscoveryControl(8)]);
+    }
+}
@@ -0,0 +1,244 @@
+//! Relay Agent Information (option 82, RFC 3046) and its sub-options
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::str;
+
+use {Result, Error};
+use options::{authentication};
+use options::parse::{sub_options};
+use options::encode::{sub_options as encode_sub_options};
+use util::{u32_bytes, ip};
+
+/// A sub-option of the Relay Agent Information option
+#[derive(Debug, PartialEq, Clone)]
+pub enum RelayAgentSubOption {
+    /// 1: identifies the circuit the request came in on
+    CircuitId(Vec<u8>),
+    /// 2: identifies the remote host end of the circuit
+    RemoteId(Vec<u8>),
+    /// 5: the subnet the client is on (RFC 3527)
+    LinkSelection(IpAddr),
+    /// 6: NVT ASCII subscriber identifier (RFC 3993)
+    SubscriberId(String),
+    /// 7: RADIUS attributes (RFC 4014), as `(type, value)` pairs
+    RadiusAttributes(Vec<(u8, Vec<u8>)>),
+    /// 8: authentication of the relay agent information (RFC 4030), in
+    /// the same format as option 90
+    Authentication(authentication::Authentication),
+    /// 9: vendor-specific information (RFC 4243)
+    VendorSpecific(Vec<VendorSpecificInformation>),
+    /// 10: relay agent flags (RFC 5010)
+    Flags(u8),
+    /// 11: address the client should use as the server identifier (RFC 5107)
+    ServerIdOverride(IpAddr),
+    /// 19: the relay agent listens on a port other than 67 (RFC 8357)
+    RelaySourcePort,
+    /// 151: virtual subnet selection (RFC 6607)
+    VirtualSubnetSelection { vss_type: u8, info: Vec<u8> },
+    /// 152: virtual subnet selection control (RFC 6607)
+    VirtualSubnetSelectionControl,
+    /// Any sub-option we don't know how to decode
+    Unknown { code: u8, data: Vec<u8> },
+}
+
+/// Bit in the `Flags` sub-option that says the request was unicast
+pub const FLAG_UNICAST: u8 = 0x80;
+
+/// Data for one enterprise, as carried by RFC 4243 vendor-specific
+/// sub-options (and by option 125)
+#[derive(Debug, PartialEq, Clone)]
+pub struct VendorSpecificInformation {
+    pub enterprise_number: u32,
+    /// `(code, data)` pairs, whose meaning is up to the vendor
+    pub sub_options: Vec<(u8, Vec<u8>)>,
+}
+
+/// Splits an option 82 payload into its sub-options. Returns `None`
+/// if the sub-options don't exactly fill `bytes`, or one of them is
+/// malformed.
+pub fn parse(bytes: &[u8]) -> Option<Vec<RelayAgentSubOption>> {
+    let mut subs = vec![];
+    for (code, data) in try_opt!(sub_options(bytes)).into_iter() {
+        subs.push(try_opt!(parse_sub_option(code, data)));
+    }
+    Some(subs)
+}
+
+fn parse_sub_option(code: u8, data: Vec<u8>) -> Option<RelayAgentSubOption> {
+    use self::RelayAgentSubOption::*;
+    Some(match code {
+        1 => CircuitId(data),
+        2 => RemoteId(data),
+        5 => LinkSelection(try_opt!(ipv4(&data))),
+        6 => SubscriberId(try_opt!(str::from_utf8(&data).ok()).to_owned()),
+        7 => RadiusAttributes(try_opt!(radius_attributes(&data))),
+        8 => Authentication(try_opt!(authentication::parse(&data))),
+        9 => VendorSpecific(try_opt!(vendor_specific_information(&data))),
+        10 if data.len() == 1 => Flags(data[0]),
+        11 => ServerIdOverride(try_opt!(ipv4(&data))),
+        19 if data.is_empty() => RelaySourcePort,
+        151 if data.len() >= 1 => VirtualSubnetSelection { vss_type: data[0], info: data[1..].to_vec() },
+        152 if data.is_empty() => VirtualSubnetSelectionControl,
+        10 | 19 | 151 | 152 => return None,
+        _ => Unknown { code: code, data: data },
+    })
+}
+
+fn ipv4(data: &[u8]) -> Option<IpAddr> {
+    if data.len() != 4 {
+        return None;
+    }
+    Some(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3])))
+}
+
+/// RADIUS attributes are `[type, length, value...]`, where the length
+/// counts the two header bytes as well
+fn radius_attributes(bytes: &[u8]) -> Option<Vec<(u8, Vec<u8>)>> {
+    let mut attrs = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        if rest.len() < 2 || rest[1] < 2 || rest.len() < rest[1] as usize {
+            return None;
+        }
+        let end = rest[1] as usize;
+        attrs.push((rest[0], rest[2..end].to_vec()));
+        rest = &rest[end..];
+    }
+    Some(attrs)
+}
+
+/// `[enterprise-number(4), data-len, sub-options...]` blocks, as used
+/// by RFC 4243 and RFC 3925
+pub fn vendor_specific_information(bytes: &[u8]) -> Option<Vec<VendorSpecificInformation>> {
+    let mut blocks = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        if rest.len() < 5 || rest.len() < 5 + rest[4] as usize {
+            return None;
+        }
+        let end = 5 + rest[4] as usize;
+        let enterprise = rest[..4].iter().fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        blocks.push(VendorSpecificInformation {
+            enterprise_number: enterprise,
+            sub_options: try_opt!(sub_options(&rest[5..end])),
+        });
+        rest = &rest[end..];
+    }
+    Some(blocks)
+}
+
+/// Encodes the sub-options back into an option 82 payload
+pub fn encode(subs: &[RelayAgentSubOption]) -> Result<Vec<u8>> {
+    use self::RelayAgentSubOption::*;
+    let mut bytes = vec![];
+    for sub in subs {
+        let (code, data) = match *sub {
+            CircuitId(ref d) => (1u8, d.clone()),
+            RemoteId(ref d) => (2u8, d.clone()),
+            LinkSelection(ref a) => (5u8, try!(ip(a))),
+            SubscriberId(ref s) => (6u8, s.as_bytes().to_vec()),
+            RadiusAttributes(ref attrs) => {
+                let mut data = vec![];
+                for &(t, ref v) in attrs {
+                    if v.len() > 253 {
+                        return Err(Error::EncodeError(format!("RADIUS attribute {} is {} bytes long", t, v.len())));
+                    }
+                    data.push(t);
+                    data.push(v.len() as u8 + 2);
+                    data.extend(v.iter().cloned());
+                }
+                (7u8, data)
+            },
+            Authentication(ref auth) => (8u8, authentication::encode(auth)),
+            VendorSpecific(ref blocks) => (9u8, try!(encode_vendor_specific_information(82u8, blocks))),
+            Flags(f) => (10u8, vec![f]),
+            ServerIdOverride(ref a) => (11u8, try!(ip(a))),
+            RelaySourcePort => (19u8, vec![]),
+            VirtualSubnetSelection { vss_type, ref info } => {
+                let mut data = vec![vss_type];
+                data.extend(info.iter().cloned());
+                (151u8, data)
+            },
+            VirtualSubnetSelectionControl => (152u8, vec![]),
+            Unknown { code, ref data } => (code, data.clone()),
+        };
+        bytes.extend(try!(encode_sub_options(82u8, &[(code, data)])).into_iter());
+    }
+    Ok(bytes)
+}
+
+/// Inverse of `vendor_specific_information`, for the option with
+/// code `tag`
+pub fn encode_vendor_specific_information(tag: u8, blocks: &[VendorSpecificInformation]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for block in blocks {
+        let data = try!(encode_sub_options(tag, &block.sub_options));
+        if data.len() > 255 {
+            return Err(Error::EncodeError(format!("Data for enterprise {} in option {} is {} bytes long",
+                                                 block.enterprise_number, tag, data.len())));
+        }
+        bytes.extend(u32_bytes(block.enterprise_number).into_iter());
+        bytes.push(data.len() as u8);
+        bytes.extend(data.into_iter());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)] mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use super::{parse, encode, VendorSpecificInformation};
+    use options::{authentication};
+    use super::RelayAgentSubOption::*;
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = vec![1u8, 4, 0, 1, 0, 10,
+                         2, 6, 0, 17, 34, 51, 68, 85,
+                         5, 4, 10, 1, 0, 0,
+                         6, 3, 115, 117, 98,
+                         7, 6, 26, 4, 0, 0, 2, 2,
+                         8, 13, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 9, 170, 187,
+                         9, 8, 0, 0, 13, 233, 3, 1, 1, 4,
+                         10, 1, 128,
+                         11, 4, 10, 1, 0, 1,
+                         19, 0,
+                         151, 4, 0, 118, 112, 110,
+                         152, 0,
+                         200, 1, 7];
+        let subs = parse(&bytes).unwrap();
+        assert_eq!(subs, vec![
+            CircuitId(vec![0, 1, 0, 10]),
+            RemoteId(vec![0, 17, 34, 51, 68, 85]),
+            LinkSelection(IpAddr::V4(Ipv4Addr::new(10, 1, 0, 0))),
+            SubscriberId("sub".to_owned()),
+            RadiusAttributes(vec![(26, vec![0, 0]), (2, vec![])]),
+            Authentication(authentication::Authentication {
+                protocol: 1,
+                algorithm: 1,
+                rdm: 1,
+                replay_detection: 9,
+                info: vec![170, 187],
+            }),
+            VendorSpecific(vec![VendorSpecificInformation {
+                enterprise_number: 3561,
+                sub_options: vec![(1, vec![4])],
+            }]),
+            Flags(super::FLAG_UNICAST),
+            ServerIdOverride(IpAddr::V4(Ipv4Addr::new(10, 1, 0, 1))),
+            RelaySourcePort,
+            VirtualSubnetSelection { vss_type: 0, info: b"vpn".to_vec() },
+            VirtualSubnetSelectionControl,
+            Unknown { code: 200, data: vec![7] },
+        ]);
+        assert_eq!(encode(&subs).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(parse(&[1u8, 4, 0, 1]), None);
+        assert_eq!(parse(&[5u8, 3, 10, 0, 0]), None);
+        assert_eq!(parse(&[7u8, 2, 26, 1]), None);
+        assert_eq!(parse(&[10u8, 0]), None);
+        assert_eq!(parse(&[19u8, 1, 0]), None);
+    }
+}
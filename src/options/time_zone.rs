@@ -0,0 +1,454 @@
+//! Time zone options (RFC 4833): a POSIX TZ string (option 100) and a
+//! tz database name (option 101)
+
+use std::cmp;
+use std::fmt;
+use std::str;
+
+/// A POSIX TZ string, such as `EST5EDT,M3.2.0/2,M11.1.0/2`
+///
+/// Offsets are kept the way POSIX writes them, as seconds to add to
+/// local time to get UTC, so they are positive west of Greenwich. Use
+/// `utc_offset_at` for the more usual sign.
+#[derive(Debug, Clone)]
+pub struct PosixTz {
+    /// The string this was parsed from, if any. It isn't compared by
+    /// `==`, and is only sent while it still says the same as the
+    /// other fields (see `wire_string`).
+    text: String,
+    pub std_name: String,
+    pub std_offset: i32,
+    pub dst: Option<Dst>,
+}
+
+/// The daylight saving time part of a `PosixTz`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Dst {
+    pub name: String,
+    /// When left out, DST is an hour ahead of standard time
+    pub offset: Option<i32>,
+    /// When DST starts and ends. When left out, the US rules in force
+    /// since 2007 are used, like glibc does.
+    pub rules: Option<(TransitionRule, TransitionRule)>,
+}
+
+/// When a change to or from DST happens
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TransitionRule {
+    pub date: TransitionDate,
+    /// Seconds after local midnight, 2:00 unless given. Can be negative
+    /// or more than a day (RFC 8536).
+    pub time: i32,
+}
+
+/// The day in a `TransitionRule`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TransitionDate {
+    /// `Jn`: day 1 to 365, never counting February 29
+    Julian(u16),
+    /// `n`: day 0 to 365, counting February 29 in leap years
+    ZeroBasedJulian(u16),
+    /// `Mm.w.d`: day `weekday` (0 is Sunday) of week `week` (1 to 5,
+    /// where 5 means the last) of month `month` (1 to 12)
+    MonthWeekDay { month: u8, week: u8, weekday: u8 },
+}
+
+const DEFAULT_RULE_TIME: i32 = 2 * 3600;
+const DEFAULT_RULES: (TransitionRule, TransitionRule) = (
+    TransitionRule { date: TransitionDate::MonthWeekDay { month: 3, week: 2, weekday: 0 }, time: DEFAULT_RULE_TIME },
+    TransitionRule { date: TransitionDate::MonthWeekDay { month: 11, week: 1, weekday: 0 }, time: DEFAULT_RULE_TIME },
+);
+
+impl PartialEq for PosixTz {
+    fn eq(&self, other: &PosixTz) -> bool {
+        self.std_name == other.std_name && self.std_offset == other.std_offset && self.dst == other.dst
+    }
+}
+
+impl PosixTz {
+    /// A time zone built from its parts, rather than parsed
+    pub fn new(std_name: String, std_offset: i32, dst: Option<Dst>) -> PosixTz {
+        PosixTz { text: String::new(), std_name: std_name, std_offset: std_offset, dst: dst }
+    }
+
+    /// The string to send in option 100: the one this was parsed from,
+    /// so that clients get exactly what the server was configured with,
+    /// unless the fields have been changed since. Otherwise, the fields
+    /// written out by `to_string()`.
+    pub fn wire_string(&self) -> String {
+        match PosixTz::parse(&self.text) {
+            Some(ref parsed) if parsed == self => self.text.clone(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Parses a TZ string. Returns `None` if it isn't valid.
+    pub fn parse(s: &str) -> Option<PosixTz> {
+        let mut p = Parser { s: s.as_bytes(), pos: 0 };
+        let std_name = try_opt!(p.name());
+        let std_offset = try_opt!(p.offset(24));
+        let mut dst = None;
+        if !p.done() {
+            let name = try_opt!(p.name());
+            let offset = if p.peek().map_or(false, |c| c != b',') { Some(try_opt!(p.offset(24))) } else { None };
+            let rules = if p.eat(b',') {
+                let start = try_opt!(p.rule());
+                if !p.eat(b',') {
+                    return None;
+                }
+                Some((start, try_opt!(p.rule())))
+            } else {
+                None
+            };
+            dst = Some(Dst { name: name, offset: offset, rules: rules });
+        }
+        if !p.done() {
+            return None;
+        }
+        Some(PosixTz { text: s.to_owned(), std_name: std_name, std_offset: std_offset, dst: dst })
+    }
+
+    /// The offset from UTC in effect at `time`, in seconds since the
+    /// Unix epoch, as seconds east of Greenwich (so local time is UTC
+    /// plus the result)
+    pub fn utc_offset_at(&self, time: i64) -> i32 {
+        let std_east = -self.std_offset;
+        let dst = match self.dst {
+            Some(ref dst) => dst,
+            None => return std_east,
+        };
+        let dst_east = -dst.offset.unwrap_or(self.std_offset - 3600);
+        let (start, end) = dst.rules.unwrap_or(DEFAULT_RULES);
+        let year = civil_from_days((time + std_east as i64).div_euclid(86400)).0;
+        // the start is given in standard time, and the end in DST
+        let start = transition_time(year, &start) - std_east as i64;
+        let end = transition_time(year, &end) - dst_east as i64;
+        let in_dst = if start <= end {
+            start <= time && time < end
+        } else {
+            // southern hemisphere: DST spans the new year
+            !(end <= time && time < start)
+        };
+        if in_dst { dst_east } else { std_east }
+    }
+}
+
+impl fmt::Display for PosixTz {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write_name(f, &self.std_name));
+        try!(write_time(f, self.std_offset));
+        if let Some(ref dst) = self.dst {
+            try!(write_name(f, &dst.name));
+            if let Some(offset) = dst.offset {
+                try!(write_time(f, offset));
+            }
+            if let Some((ref start, ref end)) = dst.rules {
+                for rule in &[start, end] {
+                    try!(match rule.date {
+                        TransitionDate::Julian(n) => write!(f, ",J{}", n),
+                        TransitionDate::ZeroBasedJulian(n) => write!(f, ",{}", n),
+                        TransitionDate::MonthWeekDay { month, week, weekday } => write!(f, ",M{}.{}.{}", month, week, weekday),
+                    });
+                    if rule.time != DEFAULT_RULE_TIME {
+                        try!(write!(f, "/"));
+                        try!(write_time(f, rule.time));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_name(f: &mut fmt::Formatter, name: &str) -> fmt::Result {
+    if name.bytes().all(|c| (c as char).is_ascii_alphabetic()) {
+        write!(f, "{}", name)
+    } else {
+        write!(f, "<{}>", name)
+    }
+}
+
+/// `[-]h[:mm[:ss]]`, leaving off the parts that are zero
+fn write_time(f: &mut fmt::Formatter, secs: i32) -> fmt::Result {
+    if secs < 0 {
+        try!(write!(f, "-"));
+    }
+    let secs = secs.abs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    try!(write!(f, "{}", h));
+    if m != 0 || s != 0 {
+        try!(write!(f, ":{:02}", m));
+    }
+    if s != 0 {
+        try!(write!(f, ":{:02}", s));
+    }
+    Ok(())
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn done(&self) -> bool {
+        self.pos == self.s.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// At least three letters, or anything but `>` inside `<...>`
+    fn name(&mut self) -> Option<String> {
+        let start;
+        let end;
+        if self.eat(b'<') {
+            start = self.pos;
+            while self.peek().map_or(false, |c| c.is_ascii_alphanumeric() || c == b'+' || c == b'-') {
+                self.pos += 1;
+            }
+            end = self.pos;
+            if !self.eat(b'>') {
+                return None;
+            }
+        } else {
+            start = self.pos;
+            while self.peek().map_or(false, |c| c.is_ascii_alphabetic()) {
+                self.pos += 1;
+            }
+            end = self.pos;
+        }
+        if end - start < 3 {
+            return None;
+        }
+        Some(try_opt!(str::from_utf8(&self.s[start..end]).ok()).to_owned())
+    }
+
+    fn number(&mut self, max: i32) -> Option<i32> {
+        let start = self.pos;
+        let mut n = 0i32;
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            n = cmp::min(n * 10 + (c - b'0') as i32, max + 1);
+            self.pos += 1;
+        }
+        if self.pos == start || n > max {
+            return None;
+        }
+        Some(n)
+    }
+
+    /// `[+|-]hh[:mm[:ss]]`, in seconds
+    fn offset(&mut self, max_hours: i32) -> Option<i32> {
+        let sign = if self.eat(b'-') { -1 } else { self.eat(b'+'); 1 };
+        let mut secs = try_opt!(self.number(max_hours)) * 3600;
+        if self.eat(b':') {
+            secs += try_opt!(self.number(59)) * 60;
+            if self.eat(b':') {
+                secs += try_opt!(self.number(59));
+            }
+        }
+        Some(sign * secs)
+    }
+
+    fn rule(&mut self) -> Option<TransitionRule> {
+        let date = if self.eat(b'J') {
+            let n = try_opt!(self.number(365));
+            if n < 1 {
+                return None;
+            }
+            TransitionDate::Julian(n as u16)
+        } else if self.eat(b'M') {
+            let month = try_opt!(self.number(12));
+            if month < 1 || !self.eat(b'.') {
+                return None;
+            }
+            let week = try_opt!(self.number(5));
+            if week < 1 || !self.eat(b'.') {
+                return None;
+            }
+            let weekday = try_opt!(self.number(6));
+            TransitionDate::MonthWeekDay { month: month as u8, week: week as u8, weekday: weekday as u8 }
+        } else {
+            TransitionDate::ZeroBasedJulian(try_opt!(self.number(365)) as u16)
+        };
+        let time = if self.eat(b'/') { try_opt!(self.offset(167)) } else { DEFAULT_RULE_TIME };
+        Some(TransitionRule { date: date, time: time })
+    }
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn month_len(year: i64, month: u8) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u8, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, u8, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u8, day)
+}
+
+/// Seconds since the epoch of a transition in `year`, as if local time
+/// were UTC
+fn transition_time(year: i64, rule: &TransitionRule) -> i64 {
+    let jan1 = days_from_civil(year, 1, 1);
+    let day = match rule.date {
+        TransitionDate::Julian(n) => {
+            jan1 + n as i64 - 1 + if is_leap(year) && n >= 60 { 1 } else { 0 }
+        },
+        TransitionDate::ZeroBasedJulian(n) => jan1 + n as i64,
+        TransitionDate::MonthWeekDay { month, week, weekday } => {
+            let first = days_from_civil(year, month, 1);
+            // 1970-01-01 was a Thursday
+            let first_weekday = (first + 4).rem_euclid(7);
+            let mut day = 1 + (weekday as i64 - first_weekday).rem_euclid(7) + 7 * (week as i64 - 1);
+            while day > month_len(year, month) {
+                day -= 7;
+            }
+            first + day - 1
+        },
+    };
+    day * 86400 + rule.time as i64
+}
+
+/// Checks an option 101 tz database name, such as `Europe/Zurich`
+pub fn parse_tzdb_name(bytes: &[u8]) -> Option<String> {
+    let name = try_opt!(str::from_utf8(bytes).ok());
+    let valid = !name.is_empty() &&
+                name.split('/').all(|part| {
+                    !part.is_empty() && part != "." && part != ".." &&
+                    part.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-' || c == b'+' || c == b'.')
+                });
+    if !valid {
+        return None;
+    }
+    Some(name.to_owned())
+}
+
+#[cfg(test)] mod tests {
+    use super::{PosixTz, Dst, TransitionRule, parse_tzdb_name, days_from_civil, civil_from_days};
+    use super::TransitionDate::*;
+
+    #[test]
+    fn test_parse() {
+        let tz = PosixTz::parse("EST5EDT,M3.2.0/2,M11.1.0/2").unwrap();
+        assert_eq!(tz, PosixTz {
+            text: "EST5EDT,M3.2.0/2,M11.1.0/2".to_owned(),
+            std_name: "EST".to_owned(),
+            std_offset: 5 * 3600,
+            dst: Some(Dst {
+                name: "EDT".to_owned(),
+                offset: None,
+                rules: Some((TransitionRule { date: MonthWeekDay { month: 3, week: 2, weekday: 0 }, time: 7200 },
+                             TransitionRule { date: MonthWeekDay { month: 11, week: 1, weekday: 0 }, time: 7200 })),
+            }),
+        });
+        assert_eq!(tz.to_string(), "EST5EDT,M3.2.0,M11.1.0");
+
+        // the example from RFC 4833
+        let tz = PosixTz::parse("EST5EDT4,M3.2.0/02:00,M11.1.0/02:00").unwrap();
+        assert_eq!((tz.std_offset, tz.dst.as_ref().unwrap().offset), (5 * 3600, Some(4 * 3600)));
+        assert_eq!(tz.wire_string(), "EST5EDT4,M3.2.0/02:00,M11.1.0/02:00");
+        assert_eq!(tz.to_string(), "EST5EDT4,M3.2.0,M11.1.0");
+
+        // once a field changes, the original text is out of date
+        let mut tz = tz;
+        tz.std_offset = 6 * 3600;
+        assert_eq!(tz.wire_string(), "EST6EDT4,M3.2.0,M11.1.0");
+        assert_eq!(PosixTz::new("UTC".to_owned(), 0, None).wire_string(), "UTC0");
+
+        let tz = PosixTz::parse("<+0530>-5:30").unwrap();
+        assert_eq!((&tz.std_name[..], tz.std_offset, tz.dst.is_none()), ("+0530", -(5 * 3600 + 1800), true));
+        assert_eq!(tz.to_string(), "<+0530>-5:30");
+
+        let tz = PosixTz::parse("AAA3BBB2,J60/-1,300/26:30:15").unwrap();
+        assert_eq!(tz.to_string(), "AAA3BBB2,J60/-1,300/26:30:15");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for s in &["", "EST", "ES5", "EST5EDT,M3.2.0", "EST5EDT,M13.1.0,M11.1.0", "EST5EDT,M3.6.0,M11.1.0",
+                   "EST5EDT,J0,J365", "EST25", "<EST5", "EST5EDT,M3.2.0,M11.1.0x"] {
+            assert_eq!(PosixTz::parse(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_utc_offset_at() {
+        let tz = PosixTz::parse("EST5EDT,M3.2.0/2,M11.1.0/2").unwrap();
+        // 2024-03-10 06:59:59 UTC is 01:59:59 EST, a second before DST
+        let start = days_from_civil(2024, 3, 10) * 86400 + 7 * 3600;
+        assert_eq!(tz.utc_offset_at(start - 1), -5 * 3600);
+        assert_eq!(tz.utc_offset_at(start), -4 * 3600);
+        // 2024-11-03 06:00 UTC is 02:00 EDT
+        let end = days_from_civil(2024, 11, 3) * 86400 + 6 * 3600;
+        assert_eq!(tz.utc_offset_at(end - 1), -4 * 3600);
+        assert_eq!(tz.utc_offset_at(end), -5 * 3600);
+
+        // the same, with the default rules
+        assert_eq!(PosixTz::parse("EST5EDT").unwrap().utc_offset_at(start), -4 * 3600);
+
+        // southern hemisphere, with DST over the new year
+        let tz = PosixTz::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(tz.utc_offset_at(days_from_civil(2024, 1, 15) * 86400), 11 * 3600);
+        assert_eq!(tz.utc_offset_at(days_from_civil(2024, 7, 15) * 86400), 10 * 3600);
+
+        assert_eq!(PosixTz::parse("UTC0").unwrap().utc_offset_at(0), 0);
+    }
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_tzdb_name() {
+        assert_eq!(parse_tzdb_name(b"Europe/Zurich"), Some("Europe/Zurich".to_owned()));
+        assert_eq!(parse_tzdb_name(b"Etc/GMT+5"), Some("Etc/GMT+5".to_owned()));
+        assert_eq!(parse_tzdb_name(b"../etc/passwd"), None);
+        assert_eq!(parse_tzdb_name(b"Europe/"), None);
+        assert_eq!(parse_tzdb_name(b""), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Vendor-Identifying Vendor Class (option 124, RFC 3925)
+//!
+//! Its sibling, Vendor-Identifying Vendor-Specific Information (option
+//! 125), has the same layout as the RFC 4243 relay agent sub-option, and
+//! is decoded with `relay_agent::vendor_specific_information`.
+
+use {Result, Error};
+use util::{u32_bytes};
+
+/// Vendor class data for one enterprise
+#[derive(Debug, PartialEq, Clone)]
+pub struct VendorClass {
+    pub enterprise_number: u32,
+    /// Opaque vendor class data items, in the order they were sent
+    pub data: Vec<Vec<u8>>,
+}
+
+/// Splits an option 124 payload into `[enterprise-number(4), data-len,
+/// items...]` blocks, where each item is `[len, data...]`
+pub fn parse(bytes: &[u8]) -> Option<Vec<VendorClass>> {
+    let mut classes = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        if rest.len() < 5 || rest.len() < 5 + rest[4] as usize {
+            return None;
+        }
+        let end = 5 + rest[4] as usize;
+        classes.push(VendorClass {
+            enterprise_number: rest[..4].iter().fold(0u32, |acc, b| (acc << 8) | *b as u32),
+            data: try_opt!(data_items(&rest[5..end])),
+        });
+        rest = &rest[end..];
+    }
+    Some(classes)
+}
+
+fn data_items(bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut items = vec![];
+    let mut rest = bytes;
+    while rest.len() > 0 {
+        let end = 1 + rest[0] as usize;
+        if rest.len() < end {
+            return None;
+        }
+        items.push(rest[1..end].to_vec());
+        rest = &rest[end..];
+    }
+    Some(items)
+}
+
+/// Inverse of `parse`
+pub fn encode(classes: &[VendorClass]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for class in classes {
+        let mut data = vec![];
+        for item in &class.data {
+            if item.len() > 255 {
+                return Err(Error::EncodeError(format!("Vendor class data for enterprise {} is {} bytes long",
+                                                     class.enterprise_number, item.len())));
+            }
+            data.push(item.len() as u8);
+            data.extend(item.iter().cloned());
+        }
+        if data.len() > 255 {
+            return Err(Error::EncodeError(format!("Vendor classes for enterprise {} are {} bytes long",
+                                                 class.enterprise_number, data.len())));
+        }
+        bytes.extend(u32_bytes(class.enterprise_number).into_iter());
+        bytes.push(data.len() as u8);
+        bytes.extend(data.into_iter());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)] mod tests {
+    use super::{parse, encode, VendorClass};
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = vec![0u8, 0, 17, 139, 9, 4, b'd', b'o', b'c', b's', 3, b'3', b'.', b'1',
+                         0, 0, 13, 233, 0];
+        let classes = parse(&bytes).unwrap();
+        assert_eq!(classes, vec![
+            VendorClass { enterprise_number: 4491, data: vec![b"docs".to_vec(), b"3.1".to_vec()] },
+            VendorClass { enterprise_number: 3561, data: vec![] },
+        ]);
+        assert_eq!(encode(&classes).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(parse(&[0u8, 0, 17, 139]), None);
+        assert_eq!(parse(&[0u8, 0, 17, 139, 2, 4, b'd']), None);
+        assert_eq!(parse(&[0u8, 0, 17, 139, 3, 1, b'd']), None);
+    }
+}
@@ -1,4 +1,5 @@
 use std::str;
+use std::net::{IpAddr};
 use nom::{IResult};
 use super::{Result, Error};
 
@@ -6,22 +7,77 @@ pub fn take_rest(input: &[u8]) -> IResult<&[u8], &[u8]> {
     IResult::Done(b"", input)
 }
 
-#[allow(dead_code)]
-pub fn null_terminated_slice_to_string(bytes: &[u8]) -> Result<&str> {
+/// `offset` and `field` say where `bytes` came from, for the error
+pub fn null_terminated_slice_to_string<'a>(bytes: &'a [u8], offset: usize, field: &'static str) -> Result<&'a str> {
     let pos = match bytes.iter().position(|b| *b == 0u8) {
         Some(p) => p,
-        None => return Err(Error::ParseError("NO NULL TERMINATION FOUND".into())),
+        None => return Err(Error::InvalidString { offset: offset + bytes.len(), field: field }),
     };
     match str::from_utf8(&bytes[0..pos]) {
         Ok(s) => Ok(s),
-        Err(_) => Err(Error::ParseError("Could not get utf8 from bytes".into())),
+        Err(e) => Err(Error::InvalidString { offset: offset + e.valid_up_to(), field: field }),
     }
 }
 
+pub fn u16_bytes(v: u16) -> Vec<u8> {
+    vec![(v >> 8) as u8, v as u8]
+}
+
+/// Inverse of `u16_bytes`, reading the first two bytes of `bytes`
+pub fn u16_from_bytes(bytes: &[u8]) -> u16 {
+    (bytes[0] as u16) << 8 | bytes[1] as u16
+}
+
+pub fn u32_bytes(v: u32) -> Vec<u8> {
+    vec![(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]
+}
+
+/// The four bytes of an IPv4 address. DHCPv4 has nowhere to put an
+/// IPv6 address, so those are an error.
+pub fn ip(addr: &IpAddr) -> Result<Vec<u8>> {
+    match *addr {
+        IpAddr::V4(ref a) => Ok(a.octets().to_vec()),
+        IpAddr::V6(ref a) => Err(Error::EncodeError(format!("Cannot encode IPv6 address {} in a DHCPv4 packet", a))),
+    }
+}
+
+/// Checks that `s` is an absolute URI (RFC 3986), in that it has a
+/// scheme and only uses characters URIs are allowed to, and returns the
+/// scheme. This doesn't check the structure of the rest of the URI.
+pub fn uri_scheme(s: &str) -> Option<&str> {
+    let colon = try_opt!(s.find(':'));
+    let (scheme, rest) = (&s[..colon], &s[colon + 1..]);
+    let mut scheme_chars = scheme.chars();
+    if !scheme_chars.next().map_or(false, |c| c.is_ascii_alphabetic()) ||
+       !scheme_chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') {
+        return None;
+    }
+    if rest.is_empty() {
+        return None;
+    }
+    let bytes = rest.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                if i + 2 >= bytes.len() || !(bytes[i + 1] as char).is_digit(16) || !(bytes[i + 2] as char).is_digit(16) {
+                    return None;
+                }
+                i += 2;
+            },
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' => {},
+            b'-' | b'.' | b'_' | b'~' | b':' | b'/' | b'?' | b'#' | b'[' | b']' | b'@' |
+            b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => {},
+            _ => return None,
+        }
+        i += 1;
+    }
+    Some(scheme)
+}
 
 #[cfg(test)] mod tests {
 
-use super::{take_rest};
+use super::{take_rest, uri_scheme};
 use nom::{IResult};
 use std::str;
 
@@ -39,5 +95,15 @@ fn test_take_rest() {
     assert_eq!(parts(b"abcd:thisistherestofthestring"), IResult::Done(&b""[..], ("abcd", "thisistherestofthestring")));
 }
 
+#[test]
+fn test_uri_scheme() {
+    assert_eq!(uri_scheme("https://example.com/portal?x=1%20"), Some("https"));
+    assert_eq!(uri_scheme("urn:ietf:params:capport:unrestricted"), Some("urn"));
+    assert_eq!(uri_scheme("example.com/portal"), None);
+    assert_eq!(uri_scheme("https://example.com/a b"), None);
+    assert_eq!(uri_scheme("https://example.com/%2"), None);
+    assert_eq!(uri_scheme("1http://example.com"), None);
+    assert_eq!(uri_scheme("https:"), None);
+}
 
 }